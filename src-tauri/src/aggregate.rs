@@ -0,0 +1,245 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::UsageEntry;
+
+/// Length of a Claude-style usage block.
+pub const BLOCK_HOURS: i64 = 5;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Totals {
+  pub requests: u64,
+  pub input_tokens: u64,
+  pub output_tokens: u64,
+  pub cache_read_tokens: u64,
+  pub cache_write_tokens: u64,
+  pub total_tokens: u64,
+  pub cost: f64,
+}
+
+impl Totals {
+  pub fn add(&mut self, e: &UsageEntry) {
+    self.requests += 1;
+    self.input_tokens = self.input_tokens.saturating_add(e.input_tokens);
+    self.output_tokens = self.output_tokens.saturating_add(e.output_tokens);
+    self.cache_read_tokens = self.cache_read_tokens.saturating_add(e.cache_read_tokens);
+    self.cache_write_tokens = self.cache_write_tokens.saturating_add(e.cache_write_tokens);
+    self.total_tokens = self.total_tokens.saturating_add(e.total_tokens);
+    self.cost += e.cost;
+  }
+
+  pub fn from_entries<'a>(entries: impl IntoIterator<Item = &'a UsageEntry>) -> Self {
+    let mut totals = Totals::default();
+    for e in entries {
+      totals.add(e);
+    }
+    totals
+  }
+}
+
+/// Narrows entries by local calendar date (inclusive), tool, and model and
+/// project substrings.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EntryFilter {
+  #[serde(default)]
+  pub since: Option<NaiveDate>,
+  #[serde(default)]
+  pub until: Option<NaiveDate>,
+  #[serde(default)]
+  pub tool: Option<String>,
+  #[serde(default)]
+  pub model: Option<String>,
+  #[serde(default)]
+  pub project: Option<String>,
+}
+
+impl EntryFilter {
+  pub fn matches(&self, e: &UsageEntry) -> bool {
+    if self.since.is_some() || self.until.is_some() {
+      let Some(date) = e.local_time().map(|t| t.date_naive()) else {
+        return false;
+      };
+      if self.since.is_some_and(|s| date < s) || self.until.is_some_and(|u| date > u) {
+        return false;
+      }
+    }
+    if let Some(tool) = self.tool.as_deref().filter(|t| !t.is_empty()) {
+      if !e.tool.eq_ignore_ascii_case(tool) {
+        return false;
+      }
+    }
+    if let Some(model) = self.model.as_deref().filter(|m| !m.is_empty()) {
+      if !e.model.to_lowercase().contains(&model.to_lowercase()) {
+        return false;
+      }
+    }
+    if let Some(project) = self.project.as_deref().filter(|p| !p.is_empty()) {
+      let matches = e
+        .project
+        .as_deref()
+        .is_some_and(|p| p.to_lowercase().contains(&project.to_lowercase()));
+      if !matches {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+  Day,
+  Week,
+  Month,
+  Year,
+  Tool,
+  Model,
+  Project,
+  Provider,
+  /// Main conversation versus subagent requests.
+  Thread,
+}
+
+impl GroupBy {
+  pub fn parse(s: &str) -> Option<Self> {
+    serde_json::from_value(serde_json::Value::String(s.to_lowercase())).ok()
+  }
+
+  /// Grouping key for an entry; dates use local time and weeks are keyed by their Monday.
+  pub fn key(self, e: &UsageEntry) -> Option<String> {
+    match self {
+      GroupBy::Tool => Some(e.tool.clone()),
+      GroupBy::Model => Some(e.model.clone()),
+      GroupBy::Project => Some(e.project.clone().unwrap_or_else(|| "unknown".to_string())),
+      GroupBy::Provider => Some(e.provider.map_or("direct", |p| p.as_str()).to_string()),
+      GroupBy::Thread => Some(if e.sidechain { "subagent" } else { "main" }.to_string()),
+      _ => {
+        let date = e.local_time()?.date_naive();
+        Some(match self {
+          GroupBy::Day => date.format("%Y-%m-%d").to_string(),
+          GroupBy::Week => {
+            let monday = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+            monday.format("%Y-%m-%d").to_string()
+          }
+          GroupBy::Month => date.format("%Y-%m").to_string(),
+          _ => date.format("%Y").to_string(),
+        })
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AggregateRow {
+  pub key: String,
+  #[serde(flatten)]
+  pub totals: Totals,
+}
+
+/// Totals per group, sorted by key.
+pub fn aggregate(entries: &[UsageEntry], filter: &EntryFilter, group_by: GroupBy) -> Vec<AggregateRow> {
+  let mut groups: BTreeMap<String, Totals> = BTreeMap::new();
+  for e in entries.iter().filter(|e| filter.matches(e)) {
+    let Some(key) = group_by.key(e) else {
+      continue;
+    };
+    groups.entry(key).or_default().add(e);
+  }
+  groups
+    .into_iter()
+    .map(|(key, totals)| AggregateRow { key, totals })
+    .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageBlock {
+  pub start: String,
+  pub end: String,
+  pub first_activity: String,
+  pub last_activity: String,
+  pub is_active: bool,
+  pub models: Vec<String>,
+  #[serde(flatten)]
+  pub totals: Totals,
+  /// Cost per hour between the first and last request of the block.
+  pub cost_per_hour: f64,
+  pub tokens_per_minute: f64,
+  /// For the active block, current cost extrapolated to the block's end.
+  pub projected_cost: Option<f64>,
+}
+
+fn floor_to_hour(t: DateTime<Utc>) -> DateTime<Utc> {
+  t.with_minute(0)
+    .and_then(|t| t.with_second(0))
+    .and_then(|t| t.with_nanosecond(0))
+    .unwrap_or(t)
+}
+
+/// Groups entries into 5-hour blocks: a block starts at the hour of its first
+/// request and a new one begins once a request falls outside it or after a
+/// 5-hour gap. Returned oldest first.
+pub fn usage_blocks(entries: &[UsageEntry], now: DateTime<Utc>) -> Vec<UsageBlock> {
+  let mut timed: Vec<(DateTime<Utc>, &UsageEntry)> =
+    entries.iter().filter_map(|e| Some((e.utc_time()?, e))).collect();
+  timed.sort_by_key(|(t, _)| *t);
+
+  let block_len = Duration::hours(BLOCK_HOURS);
+  let mut blocks = Vec::new();
+  let mut current: Vec<(DateTime<Utc>, &UsageEntry)> = Vec::new();
+  let mut start = DateTime::<Utc>::MIN_UTC;
+
+  for (t, e) in timed {
+    let starts_new = match current.last() {
+      None => true,
+      Some((last, _)) => t - start >= block_len || t - *last >= block_len,
+    };
+    if starts_new {
+      if !current.is_empty() {
+        blocks.push(build_block(start, &current, now));
+      }
+      current.clear();
+      start = floor_to_hour(t);
+    }
+    current.push((t, e));
+  }
+  if !current.is_empty() {
+    blocks.push(build_block(start, &current, now));
+  }
+  blocks
+}
+
+fn build_block(start: DateTime<Utc>, items: &[(DateTime<Utc>, &UsageEntry)], now: DateTime<Utc>) -> UsageBlock {
+  let end = start + Duration::hours(BLOCK_HOURS);
+  let first = items.first().map(|(t, _)| *t).unwrap_or(start);
+  let last = items.last().map(|(t, _)| *t).unwrap_or(start);
+  let totals = Totals::from_entries(items.iter().map(|(_, e)| *e));
+  let models: BTreeSet<String> = items.iter().map(|(_, e)| e.model.clone()).collect();
+
+  let minutes = (last - first).num_seconds() as f64 / 60.0;
+  let (cost_per_hour, tokens_per_minute) = if minutes > 0.0 {
+    (totals.cost / minutes * 60.0, totals.total_tokens as f64 / minutes)
+  } else {
+    (0.0, 0.0)
+  };
+
+  let is_active = now < end && now - last < Duration::hours(BLOCK_HOURS);
+  let projected_cost = is_active.then(|| {
+    let remaining_hours = (end - now).num_seconds().max(0) as f64 / 3600.0;
+    totals.cost + cost_per_hour * remaining_hours
+  });
+
+  UsageBlock {
+    start: start.to_rfc3339(),
+    end: end.to_rfc3339(),
+    first_activity: first.to_rfc3339(),
+    last_activity: last.to_rfc3339(),
+    is_active,
+    models: models.into_iter().collect(),
+    totals,
+    cost_per_hour,
+    tokens_per_minute,
+    projected_cost,
+  }
+}
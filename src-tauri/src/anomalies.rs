@@ -0,0 +1,335 @@
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::currency::Converter;
+use crate::{read_config, write_config, UsageEntry};
+
+const ANOMALY_SETTINGS_FILE: &str = "anomalies.json";
+const ANOMALY_ALERTS_FILE: &str = "anomaly_alerts.json";
+/// Fewer earlier periods than this give no baseline worth comparing against.
+const MIN_BASELINE: usize = 8;
+/// Only anomalies that started within this many hours raise a notification.
+const NOTIFY_WITHIN_HOURS: i64 = 24;
+
+fn default_threshold() -> f64 {
+  3.5
+}
+
+fn default_min_cost() -> f64 {
+  1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnomalySettings {
+  /// Robust z-score (deviations above the trailing median, in scaled MADs) that flags a period.
+  #[serde(default = "default_threshold")]
+  pub threshold: f64,
+  /// Periods cheaper than this, in the display currency, are never flagged.
+  #[serde(default = "default_min_cost")]
+  pub min_cost: f64,
+  /// Show a desktop notification for new anomalies found by a scan.
+  #[serde(default)]
+  pub notify: bool,
+}
+
+impl Default for AnomalySettings {
+  fn default() -> Self {
+    Self { threshold: default_threshold(), min_cost: default_min_cost(), notify: false }
+  }
+}
+
+impl AnomalySettings {
+  fn validate(&self) -> Result<(), String> {
+    if !self.threshold.is_finite() || self.threshold <= 0.0 {
+      return Err("anomaly threshold must be positive".to_string());
+    }
+    if !self.min_cost.is_finite() || self.min_cost < 0.0 {
+      return Err("anomaly minimum cost must not be negative".to_string());
+    }
+    Ok(())
+  }
+}
+
+pub fn load_settings() -> AnomalySettings {
+  read_config(ANOMALY_SETTINGS_FILE).unwrap_or_default()
+}
+
+pub fn save_settings(settings: &AnomalySettings) -> Result<(), String> {
+  settings.validate()?;
+  write_config(ANOMALY_SETTINGS_FILE, settings)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnomalyPeriod {
+  Hour,
+  Day,
+  Session,
+}
+
+impl AnomalyPeriod {
+  /// How many earlier active periods form the baseline.
+  fn window(self) -> usize {
+    match self {
+      AnomalyPeriod::Hour => 72,
+      AnomalyPeriod::Day => 28,
+      AnomalyPeriod::Session => 50,
+    }
+  }
+
+  fn label(self) -> &'static str {
+    match self {
+      AnomalyPeriod::Hour => "hour",
+      AnomalyPeriod::Day => "day",
+      AnomalyPeriod::Session => "session",
+    }
+  }
+}
+
+/// A period whose cost or tokens stood far above the preceding ones.
+#[derive(Debug, Clone, Serialize)]
+pub struct Anomaly {
+  pub period: AnomalyPeriod,
+  /// `YYYY-MM-DD HH:00`, `YYYY-MM-DD` or the session id.
+  pub key: String,
+  /// First usage in the period, RFC 3339.
+  pub start: String,
+  pub cost: f64,
+  pub tokens: u64,
+  /// Median of the trailing window.
+  pub typical_cost: f64,
+  pub typical_tokens: f64,
+  /// Robust z-scores; `None` when the trailing window has no spread.
+  pub cost_score: Option<f64>,
+  pub token_score: Option<f64>,
+  /// Largest contributors by cost within the period.
+  pub tool: String,
+  pub model: String,
+  pub session_id: Option<String>,
+}
+
+/// Usage within one hour, day or session.
+#[derive(Default)]
+struct Observation {
+  start: Option<DateTime<Local>>,
+  cost: f64,
+  tokens: u64,
+  tools: HashMap<String, f64>,
+  models: HashMap<String, f64>,
+  sessions: HashMap<String, f64>,
+}
+
+impl Observation {
+  fn add(&mut self, e: &UsageEntry, time: DateTime<Local>) {
+    if self.start.map_or(true, |s| time < s) {
+      self.start = Some(time);
+    }
+    self.cost += e.cost;
+    self.tokens = self.tokens.saturating_add(e.total_tokens);
+    *self.tools.entry(e.tool.clone()).or_default() += e.cost;
+    *self.models.entry(e.model.clone()).or_default() += e.cost;
+    if let Some(session) = &e.session_id {
+      *self.sessions.entry(session.clone()).or_default() += e.cost;
+    }
+  }
+}
+
+fn top(map: &HashMap<String, f64>) -> Option<String> {
+  map.iter().max_by(|a, b| a.1.total_cmp(b.1)).map(|(k, _)| k.clone())
+}
+
+fn median(values: &mut [f64]) -> f64 {
+  values.sort_by(f64::total_cmp);
+  let mid = values.len() / 2;
+  if values.len() % 2 == 0 {
+    (values[mid - 1] + values[mid]) / 2.0
+  } else {
+    values[mid]
+  }
+}
+
+/// Median of `window` and how many robust standard deviations `value` lies
+/// above it: MAD scaled to match a normal distribution, or the mean absolute
+/// deviation when more than half the window is identical.
+fn robust_score(value: f64, window: &[f64]) -> (f64, Option<f64>) {
+  let mut values = window.to_vec();
+  let med = median(&mut values);
+  let mut deviations: Vec<f64> = window.iter().map(|v| (v - med).abs()).collect();
+  let mad = median(&mut deviations);
+  let scale = if mad > 0.0 {
+    1.4826 * mad
+  } else {
+    1.2533 * deviations.iter().sum::<f64>() / deviations.len() as f64
+  };
+  (med, (scale > 0.0).then(|| (value - med) / scale))
+}
+
+fn observations(entries: &[UsageEntry], period: AnomalyPeriod) -> Vec<(String, Observation)> {
+  let mut groups: HashMap<String, Observation> = HashMap::new();
+  for e in entries {
+    let Some(time) = e.local_time() else {
+      continue;
+    };
+    let key = match period {
+      AnomalyPeriod::Hour => time.format("%Y-%m-%d %H:00").to_string(),
+      AnomalyPeriod::Day => time.format("%Y-%m-%d").to_string(),
+      AnomalyPeriod::Session => match &e.session_id {
+        Some(id) => id.clone(),
+        None => continue,
+      },
+    };
+    groups.entry(key).or_default().add(e, time);
+  }
+  let mut out: Vec<(String, Observation)> = groups.into_iter().collect();
+  out.sort_by_key(|(key, obs)| (obs.start, key.clone()));
+  out
+}
+
+/// Flags hours, days and sessions whose cost or tokens exceed `threshold`
+/// robust z-scores over the trailing window of earlier active periods of the
+/// same kind. Newest first; only periods starting on or after `since`.
+pub fn detect(
+  entries: &[UsageEntry],
+  settings: &AnomalySettings,
+  since: Option<NaiveDate>,
+) -> Vec<Anomaly> {
+  let mut out = Vec::new();
+  for period in [AnomalyPeriod::Hour, AnomalyPeriod::Day, AnomalyPeriod::Session] {
+    let obs = observations(entries, period);
+    for (i, (key, o)) in obs.iter().enumerate() {
+      let Some(start) = o.start else {
+        continue;
+      };
+      if o.cost < settings.min_cost || since.is_some_and(|s| start.date_naive() < s) {
+        continue;
+      }
+      let window = &obs[i.saturating_sub(period.window())..i];
+      if window.len() < MIN_BASELINE {
+        continue;
+      }
+      let costs: Vec<f64> = window.iter().map(|(_, w)| w.cost).collect();
+      let tokens: Vec<f64> = window.iter().map(|(_, w)| w.tokens as f64).collect();
+      let (typical_cost, cost_score) = robust_score(o.cost, &costs);
+      let (typical_tokens, token_score) = robust_score(o.tokens as f64, &tokens);
+      let flagged = [cost_score, token_score]
+        .into_iter()
+        .flatten()
+        .any(|score| score >= settings.threshold);
+      if !flagged {
+        continue;
+      }
+      out.push(Anomaly {
+        period,
+        key: key.clone(),
+        start: start.to_rfc3339(),
+        cost: o.cost,
+        tokens: o.tokens,
+        typical_cost,
+        typical_tokens,
+        cost_score,
+        token_score,
+        tool: top(&o.tools).unwrap_or_default(),
+        model: top(&o.models).unwrap_or_default(),
+        session_id: match period {
+          AnomalyPeriod::Session => Some(key.clone()),
+          _ => top(&o.sessions),
+        },
+      });
+    }
+  }
+  out.sort_by(|a, b| b.start.cmp(&a.start));
+  out
+}
+
+/// Anomalies in the display currency for the app and API.
+pub fn find(entries: &[UsageEntry], since: Option<NaiveDate>) -> Vec<Anomaly> {
+  detect(&Converter::load().entries(entries), &load_settings(), since)
+}
+
+/// Shows a desktop notification for each anomaly from the last day that has
+/// not been reported yet, if enabled in the settings.
+pub fn check_and_notify(app: &tauri::AppHandle, entries: &[UsageEntry]) {
+  let settings = load_settings();
+  if !settings.notify {
+    return;
+  }
+  let now = Local::now();
+  let cutoff = now - Duration::hours(NOTIFY_WITHIN_HOURS);
+  let converter = Converter::load();
+  let anomalies = detect(&converter.entries(entries), &settings, Some(cutoff.date_naive()));
+
+  // Notified anomaly ids, with when they were notified so old ones can be dropped.
+  let mut alerts: BTreeMap<String, String> = read_config(ANOMALY_ALERTS_FILE).unwrap_or_default();
+  let before = alerts.len();
+  alerts.retain(|_, at| {
+    DateTime::parse_from_rfc3339(at).is_ok_and(|t| t.with_timezone(&Local) >= cutoff - Duration::hours(NOTIFY_WITHIN_HOURS))
+  });
+  let mut changed = alerts.len() != before;
+
+  for a in anomalies {
+    let recent = DateTime::parse_from_rfc3339(&a.start).is_ok_and(|t| t.with_timezone(&Local) >= cutoff);
+    let id = format!("{}:{}", a.period.label(), a.key);
+    if !recent || alerts.contains_key(&id) {
+      continue;
+    }
+    let title = format!("Unusual {} of usage: {}", a.period.label(), a.key);
+    let body = format!(
+      "{} vs {} typical, mostly {} ({}){}.",
+      converter.format(a.cost),
+      converter.format(a.typical_cost),
+      a.model,
+      a.tool,
+      a.session_id.as_deref().map(|s| format!(", session {s}")).unwrap_or_default()
+    );
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+      log::warn!("Failed to show anomaly notification: {e}");
+    }
+    alerts.insert(id, now.to_rfc3339());
+    changed = true;
+  }
+
+  if changed {
+    if let Err(e) = write_config(ANOMALY_ALERTS_FILE, &alerts) {
+      log::warn!("Failed to save anomaly alerts: {e}");
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(day: u32, cost: f64, model: &str, session: &str) -> UsageEntry {
+    UsageEntry {
+      timestamp: format!("2025-03-{day:02}T12:00:00+00:00"),
+      tool: "Claude".to_string(),
+      model: model.to_string(),
+      input_tokens: (cost * 1000.0) as u64,
+      total_tokens: (cost * 1000.0) as u64,
+      cost,
+      session_id: Some(session.to_string()),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn flags_a_runaway_day_with_its_session_and_model() {
+    let mut entries: Vec<UsageEntry> = (1..=20)
+      .map(|d| entry(d, 10.0 + (d % 3) as f64, "claude-sonnet-4", &format!("s{d}")))
+      .collect();
+    entries.push(entry(21, 250.0, "claude-opus-4", "loop"));
+    entries.push(entry(21, 5.0, "claude-sonnet-4", "other"));
+
+    let days: Vec<Anomaly> = detect(&entries, &AnomalySettings::default(), None)
+      .into_iter()
+      .filter(|a| a.period == AnomalyPeriod::Day)
+      .collect();
+    assert_eq!(days.len(), 1);
+    assert_eq!(days[0].cost, 255.0);
+    assert_eq!(days[0].model, "claude-opus-4");
+    assert_eq!(days[0].session_id.as_deref(), Some("loop"));
+    assert_eq!(days[0].typical_cost, 11.0);
+  }
+}
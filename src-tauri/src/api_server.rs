@@ -0,0 +1,430 @@
+use chrono::{NaiveDate, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::aggregate::{self, EntryFilter, GroupBy};
+use crate::{anomalies, cache};
+use crate::currency::Converter;
+use crate::metrics::{render_prometheus, PROMETHEUS_CONTENT_TYPE};
+use crate::sessions::{self, SessionSort};
+use crate::{
+  pricing_status, read_config, tool_calls, write_config, PricingTable, ScanState, UsageEntry, PRICING,
+  SCAN_STATE,
+};
+
+const API_SETTINGS_FILE: &str = "api_server.json";
+const DEFAULT_PORT: u16 = 4178;
+/// Requests rescan the logs at most this often and are otherwise served from
+/// the last scan, so a frequent scraper does not keep the scan lock busy.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(30);
+
+fn default_port() -> u16 {
+  DEFAULT_PORT
+}
+
+/// Opt-in localhost JSON API. Requests must carry `token` as a bearer token
+/// or a `token` query parameter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiSettings {
+  #[serde(default)]
+  pub enabled: bool,
+  #[serde(default = "default_port")]
+  pub port: u16,
+  #[serde(default)]
+  pub token: String,
+}
+
+impl Default for ApiSettings {
+  fn default() -> Self {
+    Self { enabled: false, port: DEFAULT_PORT, token: String::new() }
+  }
+}
+
+struct RunningServer {
+  server: Arc<Server>,
+  thread: JoinHandle<()>,
+}
+
+static RUNNING: Lazy<Mutex<Option<RunningServer>>> = Lazy::new(|| Mutex::new(None));
+static LAST_SCAN: Lazy<Mutex<Option<Instant>>> = Lazy::new(|| Mutex::new(None));
+
+pub fn load_settings() -> ApiSettings {
+  read_config(API_SETTINGS_FILE).unwrap_or_default()
+}
+
+/// Saves `settings` (generating a token if none is set) and restarts the server accordingly.
+pub fn apply_settings(mut settings: ApiSettings) -> Result<ApiSettings, String> {
+  if settings.port == 0 {
+    return Err("port must be between 1 and 65535".to_string());
+  }
+  if settings.token.trim().is_empty() {
+    settings.token = generate_token()?;
+  }
+  write_config(API_SETTINGS_FILE, &settings)?;
+
+  stop();
+  if settings.enabled {
+    start(&settings)?;
+  }
+  Ok(settings)
+}
+
+/// Starts the server at app launch if the user enabled it.
+pub fn start_from_settings() {
+  let settings = load_settings();
+  if !settings.enabled {
+    return;
+  }
+  if settings.token.trim().is_empty() {
+    log::warn!("Local API is enabled but has no token; not starting");
+    return;
+  }
+  if let Err(e) = start(&settings) {
+    log::warn!("Failed to start local API: {e}");
+  }
+}
+
+fn start(settings: &ApiSettings) -> Result<(), String> {
+  let server = Server::http(("127.0.0.1", settings.port)).map_err(|e| e.to_string())?;
+  let server = Arc::new(server);
+  let token = settings.token.clone();
+  let worker = Arc::clone(&server);
+  let thread = std::thread::spawn(move || {
+    for request in worker.incoming_requests() {
+      handle(request, &token);
+    }
+  });
+  log::info!("Local API listening on 127.0.0.1:{}", settings.port);
+  if let Ok(mut running) = RUNNING.lock() {
+    *running = Some(RunningServer { server, thread });
+  }
+  Ok(())
+}
+
+fn stop() {
+  let Some(running) = RUNNING.lock().ok().and_then(|mut r| r.take()) else {
+    return;
+  };
+  running.server.unblock();
+  let _ = running.thread.join();
+}
+
+/// 256 random bits from the OS, hex encoded.
+fn generate_token() -> Result<String, String> {
+  let mut bytes = [0u8; 32];
+  getrandom::getrandom(&mut bytes).map_err(|e| format!("failed to generate an API token: {e}"))?;
+  Ok(bytes.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+fn percent_decode(s: &str) -> String {
+  fn hex(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+  }
+
+  let bytes = s.as_bytes();
+  let mut out = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    match bytes[i] {
+      b'+' => out.push(b' '),
+      b'%' if i + 2 < bytes.len() => match (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+        (Some(hi), Some(lo)) => {
+          out.push(hi << 4 | lo);
+          i += 2;
+        }
+        _ => out.push(b'%'),
+      },
+      b => out.push(b),
+    }
+    i += 1;
+  }
+  String::from_utf8_lossy(&out).into_owned()
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+  query
+    .split('&')
+    .filter(|kv| !kv.is_empty())
+    .map(|kv| {
+      let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+      (percent_decode(k), percent_decode(v))
+    })
+    .collect()
+}
+
+fn parse_date(query: &HashMap<String, String>, key: &str) -> Result<Option<NaiveDate>, String> {
+  match query.get(key).filter(|v| !v.is_empty()) {
+    Some(v) => NaiveDate::parse_from_str(v, "%Y-%m-%d")
+      .map(Some)
+      .map_err(|_| format!("'{key}' must be a YYYY-MM-DD date")),
+    None => Ok(None),
+  }
+}
+
+fn filter_from_query(query: &HashMap<String, String>) -> Result<EntryFilter, String> {
+  Ok(EntryFilter {
+    since: parse_date(query, "since")?,
+    until: parse_date(query, "until")?,
+    tool: query.get("tool").cloned(),
+    model: query.get("model").cloned(),
+    project: query.get("project").cloned(),
+  })
+}
+
+/// Checks the `Authorization` header value, if any, or else the `token` query parameter.
+fn is_authorized(authorization: Option<&str>, query: &HashMap<String, String>, token: &str) -> bool {
+  let bearer = authorization.and_then(|v| v.strip_prefix("Bearer ")).map(str::trim);
+  let given = bearer.or(query.get("token").map(String::as_str));
+  given.is_some_and(|g| constant_time_eq(g.as_bytes(), token.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+fn response(status: u16, content_type: &str, body: String) -> Response<std::io::Cursor<Vec<u8>>> {
+  let content_type = Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes())
+    .expect("content type header is valid");
+  Response::from_string(body)
+    .with_status_code(status)
+    .with_header(content_type)
+}
+
+fn error_body(message: &str) -> String {
+  serde_json::json!({ "error": message }).to_string()
+}
+
+fn handle(request: Request, token: &str) {
+  let url = request.url().to_string();
+  let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+  let query = parse_query(query);
+  let authorization = request
+    .headers()
+    .iter()
+    .find(|h| h.field.equiv("Authorization"))
+    .map(|h| h.value.to_string());
+
+  let (status, content_type, body) = if request.method() != &Method::Get {
+    (405, JSON_CONTENT_TYPE, error_body("only GET is supported"))
+  } else if !is_authorized(authorization.as_deref(), &query, token) {
+    (401, JSON_CONTENT_TYPE, error_body("missing or invalid token"))
+  } else {
+    match route(path.trim_end_matches('/'), &query) {
+      Ok((content_type, body)) => (200, content_type, body),
+      Err((status, message)) => (status, JSON_CONTENT_TYPE, error_body(&message)),
+    }
+  };
+
+  if let Err(e) = request.respond(response(status, content_type, body)) {
+    log::debug!("Failed to answer local API request: {e}");
+  }
+}
+
+/// Picks up new log lines if the last rescan is older than `RESCAN_INTERVAL`,
+/// saving the index like the app's own scans.
+fn refresh(state: &mut ScanState) {
+  let Ok(mut last) = LAST_SCAN.lock() else {
+    return;
+  };
+  if last.is_some_and(|t| t.elapsed() < RESCAN_INTERVAL) {
+    return;
+  }
+  *last = Some(Instant::now());
+  if state.scan_incremental() > 0 {
+    state.save_index();
+  }
+}
+
+type RouteResult = Result<(&'static str, String), (u16, String)>;
+
+fn to_json(v: serde_json::Result<String>) -> RouteResult {
+  v.map(|body| (JSON_CONTENT_TYPE, body)).map_err(|e| (500, e.to_string()))
+}
+
+fn route(path: &str, query: &HashMap<String, String>) -> RouteResult {
+  if path == "/api/pricing" {
+    return to_json(serde_json::to_string(&pricing_status()));
+  }
+
+  let mut state = SCAN_STATE.lock().map_err(|_| (500, "scan state unavailable".to_string()))?;
+  refresh(&mut state);
+  route_entries(path, query, &state.cached_entries, &Converter::load(), &PRICING)
+}
+
+/// Answers the endpoints computed from scanned entries. Raw entries and
+/// Prometheus metrics stay in USD; aggregates use `converter`.
+fn route_entries(
+  path: &str,
+  query: &HashMap<String, String>,
+  entries: &[UsageEntry],
+  converter: &Converter,
+  pricing: &PricingTable,
+) -> RouteResult {
+  let bad_request = |e: String| (400, e);
+
+  match path {
+    "/api/entries" => {
+      let filter = filter_from_query(query).map_err(bad_request)?;
+      let matching: Vec<_> = entries.iter().filter(|e| filter.matches(e)).collect();
+      to_json(serde_json::to_string(&matching))
+    }
+    "/api/aggregates" => {
+      let filter = filter_from_query(query).map_err(bad_request)?;
+      let group_by = match query.get("group_by") {
+        Some(g) => GroupBy::parse(g).ok_or_else(|| bad_request(format!("unknown group_by '{g}'")))?,
+        None => GroupBy::Day,
+      };
+      let converted = converter.entries(entries);
+      to_json(serde_json::to_string(&aggregate::aggregate(&converted, &filter, group_by)))
+    }
+    "/api/cache" => {
+      let filter = filter_from_query(query).map_err(bad_request)?;
+      let group_by = match query.get("group_by") {
+        Some(g) => GroupBy::parse(g).ok_or_else(|| bad_request(format!("unknown group_by '{g}'")))?,
+        None => GroupBy::Day,
+      };
+      to_json(serde_json::to_string(&cache::cache_metrics(entries, &filter, group_by, converter, pricing)))
+    }
+    "/api/anomalies" => {
+      let since = parse_date(query, "since").map_err(bad_request)?;
+      to_json(serde_json::to_string(&anomalies::find(entries, since)))
+    }
+    "/api/sessions" => {
+      let filter = filter_from_query(query).map_err(bad_request)?;
+      let sort = match query.get("sort") {
+        Some(s) => SessionSort::parse(s).ok_or_else(|| bad_request(format!("unknown sort '{s}'")))?,
+        None => SessionSort::default(),
+      };
+      let number = |key: &str, default: usize| match query.get(key) {
+        Some(v) => v.parse().map_err(|_| bad_request(format!("'{key}' must be a non-negative integer"))),
+        None => Ok(default),
+      };
+      let offset = number("offset", 0)?;
+      let limit = number("limit", sessions::DEFAULT_PAGE_SIZE)?;
+      let ascending = query.get("order").is_some_and(|o| o == "asc");
+      let converted = converter.entries(entries);
+      to_json(serde_json::to_string(&sessions::list_sessions(&converted, &filter, sort, ascending, offset, limit)))
+    }
+    "/api/tools" => {
+      let filter = filter_from_query(query).map_err(bad_request)?;
+      to_json(serde_json::to_string(&tool_calls::breakdown(entries, &filter, converter, pricing)))
+    }
+    "/api/blocks" => {
+      let converted = converter.entries(entries);
+      to_json(serde_json::to_string(&aggregate::usage_blocks(&converted, Utc::now())))
+    }
+    "/metrics" => Ok((PROMETHEUS_CONTENT_TYPE, render_prometheus(entries))),
+    _ => Err((404, format!("no such endpoint '{path}'"))),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn query(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+  }
+
+  fn entry(timestamp: &str, tool: &str, model: &str) -> UsageEntry {
+    UsageEntry {
+      timestamp: timestamp.to_string(),
+      tool: tool.to_string(),
+      model: model.to_string(),
+      input_tokens: 100,
+      total_tokens: 100,
+      cost: 0.5,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn decodes_percent_escapes_and_plus() {
+    assert_eq!(percent_decode("a+b%20c%2Fd"), "a b c/d");
+    assert_eq!(percent_decode("%E2%82%AC"), "€");
+    // Malformed or truncated escapes are kept as they are.
+    assert_eq!(percent_decode("100%zz"), "100%zz");
+    assert_eq!(percent_decode("50%"), "50%");
+    assert_eq!(percent_decode("%4"), "%4");
+  }
+
+  #[test]
+  fn parses_query_pairs() {
+    let q = parse_query("tool=Claude&project=my%20app&flag&&model=");
+    assert_eq!(q, query(&[("tool", "Claude"), ("project", "my app"), ("flag", ""), ("model", "")]));
+    assert!(parse_query("").is_empty());
+  }
+
+  #[test]
+  fn accepts_bearer_or_query_token() {
+    let none = HashMap::new();
+    assert!(is_authorized(Some("Bearer secret"), &none, "secret"));
+    assert!(is_authorized(Some("Bearer  secret "), &none, "secret"));
+    assert!(is_authorized(None, &query(&[("token", "secret")]), "secret"));
+    assert!(!is_authorized(None, &none, "secret"));
+    assert!(!is_authorized(Some("Bearer wrong"), &query(&[("token", "secret")]), "secret"));
+    assert!(!is_authorized(Some("Basic secret"), &none, "secret"));
+    assert!(!is_authorized(None, &query(&[("token", "secre")]), "secret"));
+
+    assert!(constant_time_eq(b"abc", b"abc"));
+    assert!(!constant_time_eq(b"abc", b"abd"));
+    assert!(!constant_time_eq(b"abc", b"abcd"));
+  }
+
+  #[test]
+  fn rejects_requests_without_a_token() {
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://127.0.0.1:{}/api/entries", server.server_addr().to_ip().unwrap().port());
+    let worker = std::thread::spawn(move || {
+      for _ in 0..2 {
+        handle(server.recv().unwrap(), "secret");
+      }
+    });
+
+    let client = reqwest::blocking::Client::new();
+    let resp = client.get(&url).send().unwrap();
+    assert_eq!(resp.status().as_u16(), 401);
+    assert_eq!(resp.text().unwrap(), r#"{"error":"missing or invalid token"}"#);
+    let resp = client.post(&url).bearer_auth("secret").send().unwrap();
+    assert_eq!(resp.status().as_u16(), 405);
+    worker.join().unwrap();
+  }
+
+  #[test]
+  fn routes_filters_and_errors() {
+    let entries = [
+      entry("2025-03-01T12:00:00Z", "Claude", "claude-sonnet-4"),
+      entry("2025-03-05T12:00:00Z", "Codex", "gpt-5"),
+      entry("2025-03-09T12:00:00Z", "Claude", "claude-opus-4"),
+    ];
+    let pricing = PricingTable::new(HashMap::new());
+    let get = |path: &str, pairs: &[(&str, &str)]| {
+      route_entries(path, &query(pairs), &entries, &Converter::usd(), &pricing)
+    };
+
+    let (content_type, body) = get("/api/entries", &[("tool", "claude"), ("since", "2025-03-04")]).unwrap();
+    assert_eq!(content_type, JSON_CONTENT_TYPE);
+    let found: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0]["model"], "claude-opus-4");
+
+    let (_, body) = get("/api/aggregates", &[("group_by", "tool")]).unwrap();
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&body).unwrap().as_array().unwrap().len(), 2);
+
+    let (content_type, body) = get("/metrics", &[]).unwrap();
+    assert_eq!(content_type, PROMETHEUS_CONTENT_TYPE);
+    assert!(body.contains("token_viewer_requests_total{tool=\"Codex\",model=\"gpt-5\"} 1"));
+
+    assert_eq!(get("/api/entries", &[("since", "March")]).unwrap_err().0, 400);
+    assert_eq!(get("/api/aggregates", &[("group_by", "hour")]).unwrap_err().0, 400);
+    assert_eq!(get("/api/sessions", &[("limit", "-1")]).unwrap_err().0, 400);
+    assert_eq!(get("/api/nothing", &[]).unwrap_err(), (404, "no such endpoint '/api/nothing'".to_string()));
+  }
+}
@@ -0,0 +1,360 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use tauri_plugin_notification::NotificationExt;
+
+use crate::{app_cache_dir, app_config_dir, read_config, write_atomic, write_config, UsageEntry};
+
+const BUDGETS_FILE: &str = "budgets.json";
+const BUDGET_ALERTS_FILE: &str = "budget_alerts.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetPeriod {
+  Daily,
+  Weekly,
+  Monthly,
+}
+
+impl BudgetPeriod {
+  /// Local-time bounds `[start, end)` of the period containing `now`. Weeks start on Monday.
+  pub fn bounds(self, now: DateTime<Local>) -> (DateTime<Local>, DateTime<Local>) {
+    let today = now.date_naive();
+    let (start, end) = match self {
+      BudgetPeriod::Daily => (today, today + Duration::days(1)),
+      BudgetPeriod::Weekly => {
+        let start = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+        (start, start + Duration::days(7))
+      }
+      BudgetPeriod::Monthly => {
+        let start = today.with_day(1).unwrap_or(today);
+        let end = if start.month() == 12 {
+          NaiveDate::from_ymd_opt(start.year() + 1, 1, 1)
+        } else {
+          NaiveDate::from_ymd_opt(start.year(), start.month() + 1, 1)
+        };
+        (start, end.unwrap_or(start + Duration::days(31)))
+      }
+    };
+    (local_midnight(start), local_midnight(end))
+  }
+}
+
+pub fn local_midnight(date: NaiveDate) -> DateTime<Local> {
+  let naive = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+  Local
+    .from_local_datetime(&naive)
+    .earliest()
+    .unwrap_or_else(|| Local.from_utc_datetime(&naive))
+}
+
+fn default_thresholds() -> Vec<f64> {
+  vec![50.0, 80.0, 100.0]
+}
+
+/// A spending limit in USD over a calendar period, optionally narrowed to one
+/// tool and/or models whose name contains `model` (case-insensitive).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Budget {
+  pub id: String,
+  #[serde(default)]
+  pub name: String,
+  pub period: BudgetPeriod,
+  pub limit: f64,
+  #[serde(default)]
+  pub tool: Option<String>,
+  #[serde(default)]
+  pub model: Option<String>,
+  /// Percentages of `limit` that raise a notification when first crossed in a period.
+  #[serde(default = "default_thresholds")]
+  pub thresholds: Vec<f64>,
+}
+
+impl Budget {
+  fn validate(&self) -> Result<(), String> {
+    if self.id.trim().is_empty() {
+      return Err("budget is missing an id".to_string());
+    }
+    if !self.limit.is_finite() || self.limit <= 0.0 {
+      return Err(format!("budget '{}' needs a positive limit", self.id));
+    }
+    if self.thresholds.iter().any(|t| !t.is_finite() || *t <= 0.0) {
+      return Err(format!("budget '{}' has a non-positive threshold", self.id));
+    }
+    Ok(())
+  }
+
+  pub fn matches(&self, entry: &UsageEntry) -> bool {
+    if let Some(tool) = self.tool.as_deref().filter(|t| !t.is_empty()) {
+      if !entry.tool.eq_ignore_ascii_case(tool) {
+        return false;
+      }
+    }
+    if let Some(model) = self.model.as_deref().filter(|m| !m.is_empty()) {
+      if !entry.model.to_lowercase().contains(&model.to_lowercase()) {
+        return false;
+      }
+    }
+    true
+  }
+
+  fn display_name(&self) -> &str {
+    if self.name.trim().is_empty() { &self.id } else { &self.name }
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetStatus {
+  pub budget: Budget,
+  pub period_start: String,
+  pub period_end: String,
+  pub spent: f64,
+  pub remaining: f64,
+  pub percent: f64,
+  /// Highest configured threshold reached so far in this period.
+  pub crossed_threshold: Option<f64>,
+}
+
+pub fn load_budgets() -> Vec<Budget> {
+  read_config(BUDGETS_FILE).unwrap_or_default()
+}
+
+pub fn save_budgets(budgets: &[Budget]) -> Result<(), String> {
+  let mut ids = HashSet::new();
+  for budget in budgets {
+    budget.validate()?;
+    if !ids.insert(budget.id.as_str()) {
+      return Err(format!("duplicate budget id '{}'", budget.id));
+    }
+  }
+  write_config(BUDGETS_FILE, budgets)
+}
+
+pub fn evaluate(budgets: &[Budget], entries: &[UsageEntry], now: DateTime<Local>) -> Vec<BudgetStatus> {
+  budgets
+    .iter()
+    .map(|budget| {
+      let (start, end) = budget.period.bounds(now);
+      let spent: f64 = entries
+        .iter()
+        .filter(|e| budget.matches(e))
+        .filter(|e| e.local_time().is_some_and(|t| t >= start && t < end))
+        .map(|e| e.cost)
+        .sum();
+      let percent = spent / budget.limit * 100.0;
+      let crossed_threshold = budget
+        .thresholds
+        .iter()
+        .copied()
+        .filter(|t| percent >= *t)
+        .reduce(f64::max);
+      BudgetStatus {
+        budget: budget.clone(),
+        period_start: start.to_rfc3339(),
+        period_end: end.to_rfc3339(),
+        spent,
+        remaining: (budget.limit - spent).max(0.0),
+        percent,
+        crossed_threshold,
+      }
+    })
+    .collect()
+}
+
+/// Highest threshold already notified for a budget, per period.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AlertMark {
+  period_start: String,
+  threshold: f64,
+}
+
+type AlertMarks = HashMap<String, AlertMark>;
+
+/// Sent alerts are state rather than settings, so they live next to the scan
+/// index. Marks written by older versions to the config directory are still
+/// read until the next alert moves them.
+fn load_alerts() -> AlertMarks {
+  let Some(path) = app_cache_dir().map(|d| d.join(BUDGET_ALERTS_FILE)) else {
+    return AlertMarks::default();
+  };
+  let Ok(raw) = fs::read(&path) else {
+    return read_config(BUDGET_ALERTS_FILE).unwrap_or_default();
+  };
+  serde_json::from_slice(&raw).unwrap_or_else(|e| {
+    log::warn!("Failed to parse {}: {e}", path.display());
+    AlertMarks::default()
+  })
+}
+
+fn save_alerts(alerts: &AlertMarks) -> Result<(), String> {
+  let path = app_cache_dir().ok_or("no cache directory available")?.join(BUDGET_ALERTS_FILE);
+  let json = serde_json::to_vec(alerts).map_err(|e| e.to_string())?;
+  write_atomic(&path, &json)?;
+  if let Some(legacy) = app_config_dir().map(|d| d.join(BUDGET_ALERTS_FILE)) {
+    let _ = fs::remove_file(legacy);
+  }
+  Ok(())
+}
+
+/// Statuses whose highest crossed threshold was not yet notified in their
+/// current period, with that threshold. Marks them as notified in `alerts`.
+fn newly_crossed(statuses: Vec<BudgetStatus>, alerts: &mut AlertMarks) -> Vec<(BudgetStatus, f64)> {
+  let mut out = Vec::new();
+  for status in statuses {
+    let Some(threshold) = status.crossed_threshold else {
+      continue;
+    };
+    let already = alerts
+      .get(&status.budget.id)
+      .is_some_and(|m| m.period_start == status.period_start && m.threshold >= threshold);
+    if already {
+      continue;
+    }
+    alerts.insert(
+      status.budget.id.clone(),
+      AlertMark { period_start: status.period_start.clone(), threshold },
+    );
+    out.push((status, threshold));
+  }
+  out
+}
+
+/// Evaluates all budgets against `entries` and shows a desktop notification for
+/// each threshold newly crossed in the current period.
+pub fn check_and_notify(app: &tauri::AppHandle, entries: &[UsageEntry]) {
+  let budgets = load_budgets();
+  if budgets.is_empty() {
+    return;
+  }
+
+  let mut alerts = load_alerts();
+  let crossed = newly_crossed(evaluate(&budgets, entries, Local::now()), &mut alerts);
+  if crossed.is_empty() {
+    return;
+  }
+
+  for (status, threshold) in &crossed {
+    let name = status.budget.display_name();
+    let title = if *threshold >= 100.0 {
+      format!("Budget exceeded: {name}")
+    } else {
+      format!("Budget {threshold:.0}% used: {name}")
+    };
+    let body = format!(
+      "${:.2} of ${:.2} spent this {} ({:.0}%).",
+      status.spent,
+      status.budget.limit,
+      match status.budget.period {
+        BudgetPeriod::Daily => "day",
+        BudgetPeriod::Weekly => "week",
+        BudgetPeriod::Monthly => "month",
+      },
+      status.percent
+    );
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+      log::warn!("Failed to show budget notification: {e}");
+    }
+  }
+
+  if let Err(e) = save_alerts(&alerts) {
+    log::warn!("Failed to save budget alerts: {e}");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::Weekday;
+
+  fn at(y: i32, m: u32, d: u32, h: u32) -> DateTime<Local> {
+    Local.with_ymd_and_hms(y, m, d, h, 0, 0).earliest().unwrap()
+  }
+
+  fn entry(at: DateTime<Local>, tool: &str, model: &str, cost: f64) -> UsageEntry {
+    UsageEntry {
+      timestamp: at.to_rfc3339(),
+      tool: tool.to_string(),
+      model: model.to_string(),
+      cost,
+      ..Default::default()
+    }
+  }
+
+  fn budget(id: &str, period: BudgetPeriod, limit: f64) -> Budget {
+    Budget {
+      id: id.to_string(),
+      name: String::new(),
+      period,
+      limit,
+      tool: None,
+      model: None,
+      thresholds: default_thresholds(),
+    }
+  }
+
+  #[test]
+  fn periods_are_local_calendar_bounds() {
+    let (start, end) = BudgetPeriod::Daily.bounds(at(2025, 3, 10, 23));
+    assert_eq!((start, end), (at(2025, 3, 10, 0), at(2025, 3, 11, 0)));
+
+    // Wednesday 2025-01-01: the week began on Monday 2024-12-30.
+    let (start, end) = BudgetPeriod::Weekly.bounds(at(2025, 1, 1, 12));
+    assert_eq!(start.weekday(), Weekday::Mon);
+    assert_eq!((start, end), (at(2024, 12, 30, 0), at(2025, 1, 6, 0)));
+    // A Monday starts its own week, a Sunday ends the previous one.
+    assert_eq!(BudgetPeriod::Weekly.bounds(at(2025, 1, 6, 0)).0, at(2025, 1, 6, 0));
+    assert_eq!(BudgetPeriod::Weekly.bounds(at(2025, 1, 5, 23)).0, at(2024, 12, 30, 0));
+
+    assert_eq!(BudgetPeriod::Monthly.bounds(at(2024, 2, 29, 12)), (at(2024, 2, 1, 0), at(2024, 3, 1, 0)));
+    assert_eq!(BudgetPeriod::Monthly.bounds(at(2024, 12, 31, 23)), (at(2024, 12, 1, 0), at(2025, 1, 1, 0)));
+  }
+
+  #[test]
+  fn evaluates_spend_in_the_current_period_for_matching_entries() {
+    let now = at(2025, 3, 10, 12);
+    let mut sonnet = budget("sonnet", BudgetPeriod::Daily, 10.0);
+    sonnet.tool = Some("claude".to_string());
+    sonnet.model = Some("SONNET".to_string());
+    let entries = [
+      entry(at(2025, 3, 10, 0), "Claude", "claude-sonnet-4", 4.0),
+      entry(at(2025, 3, 10, 11), "Claude", "claude-sonnet-4", 4.5),
+      // Yesterday, another model and another tool are left out.
+      entry(at(2025, 3, 9, 23), "Claude", "claude-sonnet-4", 100.0),
+      entry(at(2025, 3, 10, 9), "Claude", "claude-opus-4", 100.0),
+      entry(at(2025, 3, 10, 9), "OpenCode", "claude-sonnet-4", 100.0),
+    ];
+
+    let status = &evaluate(&[sonnet], &entries, now)[0];
+    assert_eq!(status.spent, 8.5);
+    assert_eq!(status.remaining, 1.5);
+    assert_eq!(status.percent, 85.0);
+    assert_eq!(status.crossed_threshold, Some(80.0));
+    assert_eq!(status.period_start, at(2025, 3, 10, 0).to_rfc3339());
+
+    let over = &evaluate(&[budget("all", BudgetPeriod::Monthly, 100.0)], &entries, now)[0];
+    assert_eq!((over.remaining, over.crossed_threshold), (0.0, Some(100.0)));
+  }
+
+  #[test]
+  fn notifies_each_threshold_once_per_period() {
+    let budgets = [budget("daily", BudgetPeriod::Daily, 10.0)];
+    let mut alerts = AlertMarks::default();
+    let mut crossed = |entries: &[UsageEntry], now| -> Vec<f64> {
+      newly_crossed(evaluate(&budgets, entries, now), &mut alerts).into_iter().map(|(_, t)| t).collect()
+    };
+    let morning = at(2025, 3, 10, 9);
+    let mut entries = vec![entry(morning, "Claude", "claude-sonnet-4", 6.0)];
+
+    assert_eq!(crossed(&entries, morning), vec![50.0]);
+    assert!(crossed(&entries, morning).is_empty());
+    // Jumping past two thresholds notifies only the highest.
+    entries.push(entry(morning, "Claude", "claude-sonnet-4", 5.0));
+    assert_eq!(crossed(&entries, morning), vec![100.0]);
+    assert!(crossed(&entries, morning).is_empty());
+    // A new day is a new period.
+    let tomorrow = at(2025, 3, 11, 9);
+    entries.push(entry(tomorrow, "Claude", "claude-sonnet-4", 5.0));
+    assert_eq!(crossed(&entries, tomorrow), vec![50.0]);
+  }
+}
@@ -0,0 +1,420 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::cost_mode::CostMode;
+use crate::{
+  app_cache_dir, app_config_dir, host_name, read_config, user_name, write_atomic, write_config, UsageEntry,
+  PRICING,
+};
+
+/// Identifies a file as a usage bundle regardless of its extension.
+pub const BUNDLE_FORMAT: &str = "token-viewer-bundle";
+pub const BUNDLE_VERSION: u32 = 1;
+
+const MACHINE_FILE: &str = "machine.json";
+const IMPORTS_FILE: &str = "imported_bundles.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MachineInfo {
+  id: String,
+}
+
+type ImportStore = HashMap<String, ImportedMachine>;
+
+/// Imported entries are data rather than settings, so they live next to the
+/// scan index. Stores written by older versions to the config directory are
+/// still read until the next import moves them.
+fn load_imports() -> ImportStore {
+  let Some(path) = app_cache_dir().map(|d| d.join(IMPORTS_FILE)) else {
+    return ImportStore::default();
+  };
+  let Ok(raw) = fs::read(&path) else {
+    return read_config(IMPORTS_FILE).unwrap_or_default();
+  };
+  serde_json::from_slice(&raw).unwrap_or_else(|e| {
+    log::warn!("Failed to parse {}: {e}", path.display());
+    ImportStore::default()
+  })
+}
+
+fn save_imports(store: &ImportStore) -> Result<(), String> {
+  let path = app_cache_dir().ok_or("no cache directory available")?.join(IMPORTS_FILE);
+  let json = serde_json::to_vec(store).map_err(|e| e.to_string())?;
+  write_atomic(&path, &json)?;
+  if let Some(legacy) = app_config_dir().map(|d| d.join(IMPORTS_FILE)) {
+    let _ = fs::remove_file(legacy);
+  }
+  Ok(())
+}
+
+/// 128 random bits from the OS, hex encoded. The id is not a secret, so if
+/// the OS has no randomness to offer the clock stands in for it.
+fn new_machine_id() -> String {
+  let mut bytes = [0u8; 16];
+  if let Err(e) = getrandom::getrandom(&mut bytes) {
+    log::warn!("Failed to read random bytes for the machine id: {e}");
+    let nanos = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+    bytes[..8].copy_from_slice(&nanos.to_le_bytes());
+  }
+  bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Stable random id of this installation, created on first use.
+pub fn machine_id() -> String {
+  if let Some(info) = read_config::<MachineInfo>(MACHINE_FILE).filter(|m| !m.id.trim().is_empty()) {
+    return info.id;
+  }
+  let info = MachineInfo { id: new_machine_id() };
+  if let Err(e) = write_config(MACHINE_FILE, &info) {
+    log::warn!("Failed to save machine id: {e}");
+  }
+  info.id
+}
+
+/// Per-tool summary of the entries in a bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceMeta {
+  pub tool: String,
+  pub entries: usize,
+  pub first_timestamp: Option<String>,
+  pub last_timestamp: Option<String>,
+}
+
+/// Everything in a bundle except the entries. In JSONL bundles this is the
+/// first line and each following line is one `UsageEntry`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleHeader {
+  pub format: String,
+  pub version: u32,
+  pub machine_id: String,
+  #[serde(default)]
+  pub host_name: String,
+  #[serde(default)]
+  pub user_name: String,
+  /// Who the usage belongs to in team reports; defaults to the OS user name.
+  #[serde(default)]
+  pub user_id: Option<String>,
+  pub exported_at: String,
+  #[serde(default)]
+  pub sources: Vec<SourceMeta>,
+}
+
+impl BundleHeader {
+  /// User the bundle's entries are attributed to.
+  pub fn user(&self) -> &str {
+    [self.user_id.as_deref(), Some(self.user_name.as_str())]
+      .into_iter()
+      .flatten()
+      .map(str::trim)
+      .find(|u| !u.is_empty() && *u != "unknown")
+      .unwrap_or(&self.machine_id)
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bundle {
+  #[serde(flatten)]
+  pub header: BundleHeader,
+  pub entries: Vec<UsageEntry>,
+}
+
+impl Bundle {
+  /// Bundles the entries recorded on this machine; imported entries are left
+  /// out so they are never re-exported under the wrong machine.
+  pub fn from_local(entries: &[UsageEntry]) -> Bundle {
+    let entries: Vec<UsageEntry> = entries.iter().filter(|e| e.machine_id.is_none()).cloned().collect();
+    let mut sources: BTreeMap<&str, SourceMeta> = BTreeMap::new();
+    for e in &entries {
+      let meta = sources.entry(&e.tool).or_insert_with(|| SourceMeta {
+        tool: e.tool.clone(),
+        entries: 0,
+        first_timestamp: None,
+        last_timestamp: None,
+      });
+      meta.entries += 1;
+      match &meta.first_timestamp {
+        Some(t) if *t <= e.timestamp => {}
+        _ => meta.first_timestamp = Some(e.timestamp.clone()),
+      }
+      match &meta.last_timestamp {
+        Some(t) if *t >= e.timestamp => {}
+        _ => meta.last_timestamp = Some(e.timestamp.clone()),
+      }
+    }
+    let header = BundleHeader {
+      format: BUNDLE_FORMAT.to_string(),
+      version: BUNDLE_VERSION,
+      machine_id: machine_id(),
+      host_name: host_name(),
+      user_name: user_name(),
+      user_id: None,
+      exported_at: Utc::now().to_rfc3339(),
+      sources: sources.into_values().collect(),
+    };
+    Bundle { header, entries }
+  }
+}
+
+fn is_jsonl(path: &Path) -> bool {
+  path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("jsonl"))
+}
+
+/// Writes `bundle` as JSONL when `path` ends in `.jsonl`, otherwise as one JSON document.
+pub fn write_bundle(path: &Path, bundle: &Bundle) -> Result<(), String> {
+  let file = fs::File::create(path).map_err(|e| format!("{}: {e}", path.display()))?;
+  let mut out = BufWriter::new(file);
+  if is_jsonl(path) {
+    serde_json::to_writer(&mut out, &bundle.header).map_err(|e| e.to_string())?;
+    writeln!(out).map_err(|e| e.to_string())?;
+    for entry in &bundle.entries {
+      serde_json::to_writer(&mut out, entry).map_err(|e| e.to_string())?;
+      writeln!(out).map_err(|e| e.to_string())?;
+    }
+  } else {
+    serde_json::to_writer(&mut out, bundle).map_err(|e| e.to_string())?;
+  }
+  out.flush().map_err(|e| e.to_string())
+}
+
+fn check_header(header: &BundleHeader) -> Result<(), String> {
+  if header.format != BUNDLE_FORMAT {
+    return Err(format!("not a usage bundle (format '{}')", header.format));
+  }
+  if header.version > BUNDLE_VERSION {
+    return Err(format!(
+      "bundle version {} is newer than supported version {BUNDLE_VERSION}",
+      header.version
+    ));
+  }
+  if header.machine_id.trim().is_empty() {
+    return Err("bundle has no machine id".to_string());
+  }
+  Ok(())
+}
+
+/// Reads a JSON or JSONL bundle, detected from the content.
+pub fn read_bundle(path: &Path) -> Result<Bundle, String> {
+  let raw = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+  let bundle = match serde_json::from_str::<Bundle>(&raw) {
+    Ok(bundle) => bundle,
+    Err(_) => {
+      let mut lines = raw.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+      let (_, first) = lines.next().ok_or_else(|| format!("{}: empty file", path.display()))?;
+      let header: BundleHeader =
+        serde_json::from_str(first).map_err(|e| format!("{}: not a usage bundle: {e}", path.display()))?;
+      let mut entries = Vec::new();
+      for (i, line) in lines {
+        let entry = serde_json::from_str(line)
+          .map_err(|e| format!("{}: line {}: {e}", path.display(), i + 1))?;
+        entries.push(entry);
+      }
+      Bundle { header, entries }
+    }
+  };
+  check_header(&bundle.header)?;
+  Ok(bundle)
+}
+
+/// Entries imported from one other machine.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ImportedMachine {
+  host_name: String,
+  user_name: String,
+  last_import: String,
+  entries: Vec<UsageEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportSummary {
+  pub machine_id: String,
+  pub host_name: String,
+  pub added: usize,
+  pub duplicates: usize,
+}
+
+/// Identity of an entry for de-duplication across repeated imports.
+fn dedup_key(e: &UsageEntry) -> String {
+  format!(
+    "{}\u{1f}{}\u{1f}{}\u{1f}{}\u{1f}{}\u{1f}{}\u{1f}{}\u{1f}{}",
+    e.tool,
+    e.timestamp,
+    e.model,
+    e.session_id.as_deref().unwrap_or(""),
+    e.input_tokens,
+    e.output_tokens,
+    e.cache_read_tokens,
+    e.cache_write_tokens
+  )
+}
+
+/// Splits `incoming` into entries not yet in `stored` and a count of duplicates.
+/// Logs can legitimately repeat an identical line, so entries are matched by
+/// count: only occurrences beyond those already stored are new.
+pub fn new_entries(stored: &[UsageEntry], incoming: Vec<UsageEntry>) -> (Vec<UsageEntry>, usize) {
+  let mut counts: HashMap<String, usize> = HashMap::new();
+  for e in stored {
+    *counts.entry(dedup_key(e)).or_default() += 1;
+  }
+  let mut added = Vec::new();
+  let mut duplicates = 0;
+  for entry in incoming {
+    if let Some(count) = counts.get_mut(&dedup_key(&entry)).filter(|c| **c > 0) {
+      *count -= 1;
+      duplicates += 1;
+    } else {
+      added.push(entry);
+    }
+  }
+  (added, duplicates)
+}
+
+/// Merges a bundle into the local import store, skipping entries already
+/// imported.
+pub fn import_bundle(path: &Path) -> Result<ImportSummary, String> {
+  let bundle = read_bundle(path)?;
+  let source_machine = bundle.header.machine_id.clone();
+  if source_machine == machine_id() {
+    return Err("this bundle was exported from this machine".to_string());
+  }
+
+  let mut store = load_imports();
+  let machine = store.entry(source_machine.clone()).or_default();
+  let (mut added, duplicates) = new_entries(&machine.entries, bundle.entries);
+  for entry in &mut added {
+    entry.machine_id = Some(source_machine.clone());
+  }
+
+  machine.host_name = bundle.header.host_name.clone();
+  machine.user_name = bundle.header.user_name.clone();
+  machine.last_import = Utc::now().to_rfc3339();
+  machine.entries.extend(added.iter().cloned());
+  save_imports(&store)?;
+
+  Ok(ImportSummary {
+    machine_id: source_machine,
+    host_name: bundle.header.host_name,
+    added: added.len(),
+    duplicates,
+  })
+}
+
+/// All entries imported from other machines, tagged with their machine id and
+/// costed with `cost_mode`.
+pub fn imported_entries(cost_mode: CostMode) -> Vec<UsageEntry> {
+  let store = load_imports();
+  let mut entries: Vec<UsageEntry> = store
+    .into_iter()
+    .flat_map(|(id, machine)| {
+      machine.entries.into_iter().map(move |mut e| {
+        e.machine_id = Some(id.clone());
+        e
+      })
+    })
+    .collect();
+  // The exporting machine may have used another cost mode; logged costs travel with the entries.
+  cost_mode.apply(&mut entries, &PRICING);
+  entries
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::path::PathBuf;
+
+  fn temp_bundle(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("token-viewer-{}-bundle-{name}", std::process::id()))
+  }
+
+  fn entry(timestamp: &str, input: u64) -> UsageEntry {
+    UsageEntry {
+      timestamp: timestamp.to_string(),
+      tool: "Claude".to_string(),
+      model: "claude-sonnet-4".to_string(),
+      input_tokens: input,
+      output_tokens: 10,
+      total_tokens: input + 10,
+      cost: 0.5,
+      session_id: Some("s1".to_string()),
+      ..Default::default()
+    }
+  }
+
+  fn bundle(entries: Vec<UsageEntry>) -> Bundle {
+    Bundle {
+      header: BundleHeader {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        machine_id: "laptop-id".to_string(),
+        host_name: "laptop".to_string(),
+        user_name: "unknown".to_string(),
+        user_id: None,
+        exported_at: "2025-01-02T00:00:00+00:00".to_string(),
+        sources: Vec::new(),
+      },
+      entries,
+    }
+  }
+
+  fn keys(entries: &[UsageEntry]) -> Vec<String> {
+    entries.iter().map(dedup_key).collect()
+  }
+
+  #[test]
+  fn round_trips_json_and_jsonl() {
+    let original = bundle(vec![entry("2025-01-01T10:00:00Z", 100), entry("2025-01-01T11:00:00Z", 200)]);
+    for name in ["round-trip.json", "round-trip.jsonl"] {
+      let path = temp_bundle(name);
+      write_bundle(&path, &original).unwrap();
+      let raw = fs::read_to_string(&path).unwrap();
+      assert_eq!(raw.lines().count(), if is_jsonl(&path) { 3 } else { 1 }, "{name}");
+
+      let read = read_bundle(&path).unwrap();
+      assert_eq!(read.header.machine_id, "laptop-id");
+      // An unknown user name falls back to the machine id.
+      assert_eq!(read.header.user(), "laptop-id");
+      assert_eq!(keys(&read.entries), keys(&original.entries));
+      let _ = fs::remove_file(&path);
+    }
+  }
+
+  #[test]
+  fn rejects_newer_versions_and_other_files() {
+    let path = temp_bundle("newer.json");
+    let mut newer = bundle(Vec::new());
+    newer.header.version = BUNDLE_VERSION + 1;
+    write_bundle(&path, &newer).unwrap();
+    assert!(read_bundle(&path).unwrap_err().contains("newer than supported"));
+
+    fs::write(&path, r#"{"format":"something-else","version":1,"machine_id":"x","exported_at":""}"#).unwrap();
+    assert!(read_bundle(&path).unwrap_err().contains("not a usage bundle"));
+    fs::write(&path, "").unwrap();
+    assert!(read_bundle(&path).unwrap_err().contains("empty file"));
+    let _ = fs::remove_file(&path);
+  }
+
+  #[test]
+  fn reimporting_a_newer_bundle_adds_only_new_entries() {
+    let first = vec![entry("2025-01-01T10:00:00Z", 100), entry("2025-01-01T11:00:00Z", 200)];
+    let (stored, duplicates) = new_entries(&[], first);
+    assert_eq!((stored.len(), duplicates), (2, 0));
+
+    // The next export from the same machine repeats the earlier entries.
+    let newer = vec![
+      entry("2025-01-01T10:00:00Z", 100),
+      entry("2025-01-01T11:00:00Z", 200),
+      entry("2025-01-02T09:00:00Z", 300),
+    ];
+    let (added, duplicates) = new_entries(&stored, newer);
+    assert_eq!(keys(&added), keys(&[entry("2025-01-02T09:00:00Z", 300)]));
+    assert_eq!(duplicates, 2);
+
+    // Identical lines are matched by count, so a repeat logged since is kept.
+    let repeated = vec![entry("2025-01-01T10:00:00Z", 100), entry("2025-01-01T10:00:00Z", 100)];
+    let (added, duplicates) = new_entries(&stored, repeated);
+    assert_eq!((added.len(), duplicates), (1, 1));
+  }
+}
@@ -0,0 +1,194 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::aggregate::{EntryFilter, GroupBy};
+use crate::currency::Converter;
+use crate::sources::InputSemantics;
+use crate::{tiered_cost, PricingTable, UsageEntry};
+
+/// Prompt caching effectiveness for a group of entries.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CacheMetrics {
+  pub key: String,
+  pub requests: u64,
+  /// Input tokens billed at the full input rate.
+  pub uncached_input_tokens: u64,
+  pub cache_read_tokens: u64,
+  pub cache_write_tokens: u64,
+  /// Uncached input plus cache writes and reads.
+  pub prompt_tokens: u64,
+  /// `cache_read_tokens / prompt_tokens`.
+  pub hit_ratio: Option<f64>,
+  /// Cache reads per cache-written token, i.e. how often a written token was reused.
+  pub reads_per_write: Option<f64>,
+  /// What cache reads saved versus paying `input_cost_per_token` for the same tokens.
+  pub read_savings: f64,
+  /// What cache writes cost on top of `input_cost_per_token`.
+  pub write_premium: f64,
+  /// `read_savings - write_premium`; negative when caching cost more than it saved.
+  pub net_savings: f64,
+  /// `read_savings / write_premium`; above 1 once cache writes have paid for themselves.
+  pub write_payback: Option<f64>,
+  /// Requests whose model has no known pricing and are left out of the savings.
+  pub unpriced_requests: u64,
+}
+
+impl CacheMetrics {
+  fn add(&mut self, e: &UsageEntry, input_includes_cache: bool, converter: &Converter, pricing: &PricingTable) {
+    let uncached = if input_includes_cache {
+      e.input_tokens.saturating_sub(e.cache_read_tokens)
+    } else {
+      e.input_tokens
+    };
+    self.requests += 1;
+    self.uncached_input_tokens = self.uncached_input_tokens.saturating_add(uncached);
+    self.cache_read_tokens = self.cache_read_tokens.saturating_add(e.cache_read_tokens);
+    self.cache_write_tokens = self.cache_write_tokens.saturating_add(e.cache_write_tokens);
+
+    if e.cache_read_tokens == 0 && e.cache_write_tokens == 0 {
+      return;
+    }
+    let Some(p) = pricing.find(&e.model, e.provider).map(|p| p.for_tier(e.service_tier)) else {
+      self.unpriced_requests += 1;
+      return;
+    };
+    let date = e.local_time().map(|t| t.date_naive());
+    // A model without a cache price in the table is treated as billed at the
+    // input rate, so it neither saves nor costs extra.
+    if p.cache_read_cost > 0.0 {
+      let at_input = tiered_cost(e.cache_read_tokens, p.input_cost_per_token, p.input_cost_above_200k);
+      let actual = tiered_cost(e.cache_read_tokens, p.cache_read_cost, p.cache_read_cost_above_200k);
+      self.read_savings += converter.convert(at_input - actual, date);
+    }
+    if p.cache_write_cost > 0.0 {
+      let at_input = tiered_cost(e.cache_write_tokens, p.input_cost_per_token, p.input_cost_above_200k);
+      let actual = tiered_cost(e.cache_write_tokens, p.cache_write_cost, p.cache_write_cost_above_200k);
+      self.write_premium += converter.convert(actual - at_input, date);
+    }
+  }
+
+  fn finish(mut self) -> Self {
+    self.prompt_tokens = self
+      .uncached_input_tokens
+      .saturating_add(self.cache_read_tokens)
+      .saturating_add(self.cache_write_tokens);
+    self.hit_ratio = (self.prompt_tokens > 0).then(|| self.cache_read_tokens as f64 / self.prompt_tokens as f64);
+    self.reads_per_write =
+      (self.cache_write_tokens > 0).then(|| self.cache_read_tokens as f64 / self.cache_write_tokens as f64);
+    self.net_savings = self.read_savings - self.write_premium;
+    self.write_payback = (self.write_premium > 0.0).then(|| self.read_savings / self.write_premium);
+    self
+  }
+}
+
+/// Cache metrics over all `entries`, keyed `all`. Money is in the converter's currency.
+pub fn overall<'a>(
+  entries: impl IntoIterator<Item = &'a UsageEntry>,
+  converter: &Converter,
+  pricing: &PricingTable,
+) -> CacheMetrics {
+  let mut semantics = InputSemantics::default();
+  let mut metrics = CacheMetrics { key: "all".to_string(), ..Default::default() };
+  for e in entries {
+    metrics.add(e, semantics.includes_cache(&e.tool), converter, pricing);
+  }
+  metrics.finish()
+}
+
+/// Cache metrics per group, sorted by key. Money is in the converter's currency.
+pub fn cache_metrics(
+  entries: &[UsageEntry],
+  filter: &EntryFilter,
+  group_by: GroupBy,
+  converter: &Converter,
+  pricing: &PricingTable,
+) -> Vec<CacheMetrics> {
+  let mut semantics = InputSemantics::default();
+  let mut groups: BTreeMap<String, CacheMetrics> = BTreeMap::new();
+  for e in entries.iter().filter(|e| filter.matches(e)) {
+    let Some(key) = group_by.key(e) else {
+      continue;
+    };
+    let includes_cache = semantics.includes_cache(&e.tool);
+    groups
+      .entry(key)
+      .or_insert_with_key(|key| CacheMetrics { key: key.clone(), ..Default::default() })
+      .add(e, includes_cache, converter, pricing);
+  }
+  groups.into_values().map(CacheMetrics::finish).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::PricingInfo;
+  use std::collections::HashMap;
+
+  fn entry(tool: &str, model: &str, input: u64, cache_read: u64, cache_write: u64) -> UsageEntry {
+    UsageEntry {
+      timestamp: "2025-03-10T12:00:00Z".to_string(),
+      tool: tool.to_string(),
+      model: model.to_string(),
+      input_tokens: input,
+      cache_read_tokens: cache_read,
+      cache_write_tokens: cache_write,
+      ..Default::default()
+    }
+  }
+
+  fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-12, "{actual} != {expected}");
+  }
+
+  #[test]
+  fn computes_hit_ratio_and_savings() {
+    let pricing = PricingTable::new(HashMap::from([
+      (
+        "gpt-5".to_string(),
+        PricingInfo { input_cost_per_token: 1e-6, cache_read_cost: 1e-7, ..Default::default() },
+      ),
+      (
+        "claude-sonnet-4".to_string(),
+        PricingInfo {
+          input_cost_per_token: 3e-6,
+          cache_read_cost: 3e-7,
+          cache_write_cost: 3.75e-6,
+          ..Default::default()
+        },
+      ),
+    ]));
+    let entries = [
+      // Codex input includes its 800 cache reads.
+      entry("Codex", "gpt-5", 1000, 800, 0),
+      entry("Claude", "claude-sonnet-4", 100, 2000, 1000),
+      entry("Claude", "claude-sonnet-4", 400, 0, 0),
+      entry("Claude", "not-in-the-table", 0, 500, 0),
+    ];
+
+    let all = overall(&entries, &Converter::usd(), &pricing);
+    assert_eq!((all.uncached_input_tokens, all.cache_read_tokens, all.cache_write_tokens), (700, 3300, 1000));
+    assert_eq!((all.prompt_tokens, all.unpriced_requests), (5000, 1));
+    assert_close(all.hit_ratio.unwrap(), 0.66);
+    assert_close(all.reads_per_write.unwrap(), 3.3);
+    // 800 * (1e-6 - 1e-7) + 2000 * (3e-6 - 3e-7)
+    assert_close(all.read_savings, 0.00612);
+    // 1000 * (3.75e-6 - 3e-6)
+    assert_close(all.write_premium, 0.00075);
+    assert_close(all.net_savings, 0.00537);
+    assert_close(all.write_payback.unwrap(), 8.16);
+
+    let filter = EntryFilter::default();
+    let by_tool = cache_metrics(&entries, &filter, GroupBy::Tool, &Converter::usd(), &pricing);
+    let keys: Vec<&str> = by_tool.iter().map(|m| m.key.as_str()).collect();
+    assert_eq!(keys, ["Claude", "Codex"]);
+    assert_close(by_tool[1].hit_ratio.unwrap(), 0.8);
+    assert_eq!((by_tool[1].reads_per_write, by_tool[1].write_payback), (None, None));
+  }
+
+  #[test]
+  fn has_no_ratios_without_prompt_tokens() {
+    let none = overall(&[], &Converter::usd(), &PricingTable::new(HashMap::new()));
+    assert_eq!((none.hit_ratio, none.reads_per_write, none.write_payback), (None, None, None));
+    assert_eq!(none.net_savings, 0.0);
+  }
+}
@@ -0,0 +1,268 @@
+use chrono::{Local, NaiveDate};
+use std::fmt::Write;
+use std::path::Path;
+use std::process::ExitCode;
+
+use crate::budgets;
+use crate::bundle::{self, Bundle};
+use crate::currency::Converter;
+use crate::forecast::{self, Estimate, Forecast};
+use crate::html_report;
+use crate::mcp;
+use crate::statusline;
+use crate::team_report::{self, ReportFormat};
+use crate::{ScanState, UsageEntry, PRICING};
+
+const USAGE: &str = "\
+Usage: token-viewer-cli <command>
+
+Commands:
+  statusline    Print a one-line cost summary for a Claude Code status line.
+                Reads the status line JSON from stdin.
+  mcp           Run a Model Context Protocol server on stdio with usage query tools.
+  export <file> [--user <id>]
+                Write this machine's usage to an export bundle (.json or .jsonl).
+                `--user` sets the id used in team reports (default: OS user name).
+  import <file> Merge another machine's export bundle into the local store.
+  report <file> [--since YYYY-MM-DD] [--until YYYY-MM-DD]
+                Write a self-contained HTML summary of this machine's usage.
+  team-report <dir> [--since YYYY-MM-DD] [--until YYYY-MM-DD]
+                [--format markdown|csv|json] [--output <file>]
+                Total the bundles in <dir> per user, model and project.
+  forecast [--format text|json]
+                Project this month's cost and tokens overall, per tool and
+                per monthly budget, with an 80% confidence band.
+";
+
+/// Entry point of the `token-viewer-cli` binary.
+pub fn run() -> ExitCode {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  match args.first().map(String::as_str) {
+    Some("statusline") => statusline::run(&args),
+    Some("mcp") => match mcp::serve() {
+      Ok(()) => ExitCode::SUCCESS,
+      Err(e) => {
+        eprintln!("mcp server stopped: {e}");
+        ExitCode::FAILURE
+      }
+    },
+    Some("export") => match positional(&args) {
+      Some(path) => export(Path::new(path), flag(&args, "--user")),
+      None => usage_error("export needs an output file"),
+    },
+    Some("import") => match positional(&args) {
+      Some(path) => import(Path::new(path)),
+      None => usage_error("import needs a bundle file"),
+    },
+    Some("report") => report(&args),
+    Some("team-report") => team_report(&args),
+    Some("forecast") => forecast(&args),
+    None | Some("-h" | "--help" | "help") => {
+      print!("{USAGE}");
+      ExitCode::SUCCESS
+    }
+    Some(other) => usage_error(&format!("unknown command '{other}'")),
+  }
+}
+
+fn usage_error(message: &str) -> ExitCode {
+  eprintln!("{message}\n");
+  eprint!("{USAGE}");
+  ExitCode::from(2)
+}
+
+/// Value following `name`, e.g. `flag(args, "--since")`.
+fn flag<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+  let i = args.iter().position(|a| a == name)?;
+  args.get(i + 1).map(String::as_str)
+}
+
+/// First argument after the command that is neither a flag nor a flag's value.
+fn positional(args: &[String]) -> Option<&str> {
+  let mut rest = args.iter().skip(1);
+  while let Some(arg) = rest.next() {
+    if arg.starts_with("--") {
+      rest.next();
+    } else {
+      return Some(arg);
+    }
+  }
+  None
+}
+
+fn date_flag(args: &[String], name: &str) -> Result<Option<NaiveDate>, String> {
+  match flag(args, name) {
+    Some(v) => NaiveDate::parse_from_str(v, "%Y-%m-%d")
+      .map(Some)
+      .map_err(|_| format!("{name} must be a YYYY-MM-DD date")),
+    None => Ok(None),
+  }
+}
+
+/// Loads the persisted scan index and picks up anything logged since it was
+/// written, so repeated invocations only read new log lines.
+fn load_entries() -> Vec<UsageEntry> {
+  let mut state = ScanState::load_index();
+  if state.scan_incremental() > 0 {
+    state.save_index();
+  }
+  state.cached_entries
+}
+
+fn export(path: &Path, user: Option<&str>) -> ExitCode {
+  let mut bundle = Bundle::from_local(&load_entries());
+  bundle.header.user_id = user.map(str::to_string);
+  match bundle::write_bundle(path, &bundle) {
+    Ok(()) => {
+      println!("Exported {} entries to {}", bundle.entries.len(), path.display());
+      ExitCode::SUCCESS
+    }
+    Err(e) => {
+      eprintln!("export failed: {e}");
+      ExitCode::FAILURE
+    }
+  }
+}
+
+fn import(path: &Path) -> ExitCode {
+  match bundle::import_bundle(path) {
+    Ok(summary) => {
+      let mut state = ScanState::load_index();
+      state.reload_imports();
+      state.save_index();
+      println!(
+        "Imported {} entries from {} ({} already present)",
+        summary.added, summary.host_name, summary.duplicates
+      );
+      ExitCode::SUCCESS
+    }
+    Err(e) => {
+      eprintln!("import failed: {e}");
+      ExitCode::FAILURE
+    }
+  }
+}
+
+fn date_range(args: &[String]) -> Result<(Option<NaiveDate>, Option<NaiveDate>), String> {
+  Ok((date_flag(args, "--since")?, date_flag(args, "--until")?))
+}
+
+fn report(args: &[String]) -> ExitCode {
+  let Some(path) = positional(args) else {
+    return usage_error("report needs an output file");
+  };
+  let (since, until) = match date_range(args) {
+    Ok(range) => range,
+    Err(e) => return usage_error(&e),
+  };
+  match html_report::write(Path::new(path), &load_entries(), since, until, &Converter::load(), &PRICING) {
+    Ok(()) => {
+      println!("Wrote {path}");
+      ExitCode::SUCCESS
+    }
+    Err(e) => {
+      eprintln!("report failed: {e}");
+      ExitCode::FAILURE
+    }
+  }
+}
+
+fn team_report(args: &[String]) -> ExitCode {
+  let Some(dir) = positional(args) else {
+    return usage_error("team-report needs a directory of bundles");
+  };
+  let (since, until) = match date_range(args) {
+    Ok(range) => range,
+    Err(e) => return usage_error(&e),
+  };
+  let format = match flag(args, "--format").map(ReportFormat::parse) {
+    None => ReportFormat::Markdown,
+    Some(Some(format)) => format,
+    Some(None) => return usage_error("--format must be markdown, csv or json"),
+  };
+
+  let report = match team_report::build(Path::new(dir), since, until, &Converter::load()) {
+    Ok(report) => report,
+    Err(e) => {
+      eprintln!("team-report failed: {e}");
+      return ExitCode::FAILURE;
+    }
+  };
+  for skipped in &report.skipped {
+    eprintln!("skipped {skipped}");
+  }
+  let text = report.render(format);
+  match flag(args, "--output") {
+    Some(path) => {
+      if let Err(e) = std::fs::write(path, text) {
+        eprintln!("failed to write {path}: {e}");
+        return ExitCode::FAILURE;
+      }
+    }
+    None => print!("{text}"),
+  }
+  ExitCode::SUCCESS
+}
+
+fn estimate_text(e: &Estimate, converter: &Converter) -> String {
+  format!(
+    "{} so far, {} projected ({} to {})",
+    converter.format(e.month_to_date),
+    converter.format(e.projected),
+    converter.format(e.low),
+    converter.format(e.high)
+  )
+}
+
+fn forecast_text(f: &Forecast, converter: &Converter) -> String {
+  let mut out = format!(
+    "Forecast for {} (as of {}, {:.0}% band, {} days of history)\n",
+    f.month_start.format("%Y-%m"),
+    f.as_of,
+    f.confidence * 100.0,
+    f.history_days
+  );
+  for p in std::iter::once(&f.overall).chain(&f.tools) {
+    let _ = writeln!(
+      out,
+      "{}: {}, {:.0} tokens projected",
+      p.key,
+      estimate_text(&p.cost, converter),
+      p.tokens.projected
+    );
+  }
+  let usd = Converter::usd();
+  for b in &f.budgets {
+    let verdict = if b.certain_to_exceed {
+      "will exceed"
+    } else if b.likely_to_exceed {
+      "likely to exceed"
+    } else {
+      "within"
+    };
+    let _ = writeln!(
+      out,
+      "budget {}: {}; {verdict} {} limit",
+      b.budget_id,
+      estimate_text(&b.cost, &usd),
+      usd.format(b.limit)
+    );
+  }
+  out
+}
+
+fn forecast(args: &[String]) -> ExitCode {
+  let json = match flag(args, "--format") {
+    None | Some("text") => false,
+    Some("json") => true,
+    Some(_) => return usage_error("--format must be text or json"),
+  };
+  let converter = Converter::load();
+  let forecast = forecast::forecast(&load_entries(), &budgets::load_budgets(), Local::now(), &converter);
+  if json {
+    println!("{}", serde_json::to_string_pretty(&forecast).unwrap_or_default());
+  } else {
+    print!("{}", forecast_text(&forecast, &converter));
+  }
+  ExitCode::SUCCESS
+}
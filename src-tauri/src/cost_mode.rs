@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+
+use crate::sources::InputSemantics;
+use crate::{read_config, write_config, PricingTable, UsageEntry};
+
+const COST_MODE_FILE: &str = "cost_mode.json";
+
+/// How an entry's `cost` is derived from the cost the tool logged, if any, and
+/// the cost calculated from its tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CostMode {
+  /// Logged cost when present, otherwise calculated.
+  #[default]
+  Auto,
+  /// Always calculated from tokens, ignoring logged costs.
+  Calculate,
+  /// Only logged costs; entries without one cost nothing.
+  Display,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CostSettings {
+  #[serde(default)]
+  pub mode: CostMode,
+}
+
+pub fn load_mode() -> CostMode {
+  read_config::<CostSettings>(COST_MODE_FILE).unwrap_or_default().mode
+}
+
+pub fn save_mode(mode: CostMode) -> Result<(), String> {
+  write_config(COST_MODE_FILE, &CostSettings { mode })
+}
+
+/// Cost of `e` at `pricing`'s rates, billing input per the tool's cache
+/// semantics and adding its web searches. Unpriced models cost nothing.
+pub(crate) fn calculated_cost(e: &UsageEntry, input_includes_cache: bool, pricing: &PricingTable) -> f64 {
+  if e.model == "unknown" {
+    return 0.0;
+  }
+  let Some(p) = pricing.find(&e.model, e.provider) else {
+    return 0.0;
+  };
+  let input = if input_includes_cache {
+    e.input_tokens.saturating_sub(e.cache_read_tokens)
+  } else {
+    e.input_tokens
+  };
+  p.for_tier(e.service_tier)
+    .cost(input, e.output_tokens, e.cache_read_tokens, e.cache_write_tokens)
+    + pricing.web_search_cost(&e.model, e.provider, e.web_search_requests)
+}
+
+impl CostMode {
+  /// Recomputes `cost` for every entry from its tokens and `logged_cost`.
+  pub fn apply(self, entries: &mut [UsageEntry], pricing: &PricingTable) {
+    let mut semantics = InputSemantics::default();
+    for e in entries {
+      e.cost = match (self, e.logged_cost) {
+        (CostMode::Display, logged) => logged.unwrap_or(0.0),
+        (CostMode::Auto, Some(logged)) => logged,
+        _ => calculated_cost(e, semantics.includes_cache(&e.tool), pricing),
+      };
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::PricingInfo;
+  use std::collections::HashMap;
+
+  fn entry(tool: &str, model: &str, logged_cost: Option<f64>) -> UsageEntry {
+    UsageEntry {
+      timestamp: "2025-01-01T00:00:00Z".to_string(),
+      tool: tool.to_string(),
+      model: model.to_string(),
+      input_tokens: 1000,
+      cache_read_tokens: 400,
+      output_tokens: 100,
+      total_tokens: 1500,
+      logged_cost,
+      ..Default::default()
+    }
+  }
+
+  fn costs(mode: CostMode, entries: &mut [UsageEntry], pricing: &PricingTable) -> Vec<f64> {
+    mode.apply(entries, pricing);
+    entries.iter().map(|e| (e.cost * 1e6).round() / 1e6).collect()
+  }
+
+  #[test]
+  fn applies_logged_and_calculated_costs_per_mode() {
+    let rates = PricingInfo {
+      input_cost_per_token: 1e-3,
+      cache_read_cost: 1e-4,
+      output_cost_per_token: 2e-3,
+      ..Default::default()
+    };
+    let pricing = PricingTable::new(HashMap::from([("gpt-5".to_string(), rates)]));
+    let mut entries = [
+      entry("Codex", "gpt-5", Some(5.0)),
+      // Codex input includes its cache reads: 600 uncached, 400 cached, 100 output.
+      entry("Codex", "gpt-5", None),
+      // Claude reports cache reads apart from its 1000 input tokens.
+      entry("Claude", "gpt-5", None),
+      entry("Claude", "not-in-the-table", None),
+    ];
+
+    assert_eq!(costs(CostMode::Auto, &mut entries, &pricing), vec![5.0, 0.84, 1.24, 0.0]);
+    assert_eq!(costs(CostMode::Calculate, &mut entries, &pricing), vec![0.84, 0.84, 1.24, 0.0]);
+    // Display: entries without a logged cost become 0.
+    assert_eq!(costs(CostMode::Display, &mut entries, &pricing), vec![5.0, 0.0, 0.0, 0.0]);
+    // Costs are always recomputed, so switching back restores them.
+    assert_eq!(costs(CostMode::Auto, &mut entries, &pricing), vec![5.0, 0.84, 1.24, 0.0]);
+  }
+}
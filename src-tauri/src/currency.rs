@@ -0,0 +1,231 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+use crate::{read_config, write_config, UsageEntry};
+
+const CURRENCY_FILE: &str = "currency.json";
+pub const BASE_CURRENCY: &str = "USD";
+
+fn default_currency() -> String {
+  BASE_CURRENCY.to_string()
+}
+
+/// Units of `currency` per US dollar, in effect from `date` until the next
+/// rate for the same currency.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangeRate {
+  pub currency: String,
+  pub date: NaiveDate,
+  pub rate: f64,
+}
+
+/// Display currency and the user-supplied rate table. Costs are always stored
+/// in USD and converted when aggregated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrencySettings {
+  #[serde(default = "default_currency")]
+  pub currency: String,
+  #[serde(default)]
+  pub rates: Vec<ExchangeRate>,
+}
+
+impl Default for CurrencySettings {
+  fn default() -> Self {
+    Self { currency: default_currency(), rates: Vec::new() }
+  }
+}
+
+fn is_currency_code(code: &str) -> bool {
+  code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase())
+}
+
+impl CurrencySettings {
+  fn validate(&self) -> Result<(), String> {
+    if !is_currency_code(&self.currency) {
+      return Err(format!("'{}' is not a three-letter currency code", self.currency));
+    }
+    for r in &self.rates {
+      if !is_currency_code(&r.currency) {
+        return Err(format!("'{}' is not a three-letter currency code", r.currency));
+      }
+      if !r.rate.is_finite() || r.rate <= 0.0 {
+        return Err(format!("rate for {} on {} must be positive", r.currency, r.date));
+      }
+    }
+    if self.currency != BASE_CURRENCY && !self.rates.iter().any(|r| r.currency == self.currency) {
+      return Err(format!("no exchange rate for {}", self.currency));
+    }
+    Ok(())
+  }
+}
+
+pub fn load_settings() -> CurrencySettings {
+  read_config(CURRENCY_FILE).unwrap_or_default()
+}
+
+pub fn save_settings(settings: &CurrencySettings) -> Result<(), String> {
+  settings.validate()?;
+  write_config(CURRENCY_FILE, settings)
+}
+
+/// The display currency as sent to the frontend along with converted amounts.
+#[derive(Debug, Clone, Serialize)]
+pub struct DisplayCurrency {
+  pub code: String,
+  /// Set for the currencies with a well-known symbol; others show their code.
+  pub symbol: Option<&'static str>,
+}
+
+/// Converts USD amounts into the display currency using the rate effective on
+/// a given date.
+#[derive(Debug, Clone)]
+pub struct Converter {
+  currency: String,
+  /// Rates for `currency`, oldest first. Empty for USD.
+  rates: Vec<(NaiveDate, f64)>,
+}
+
+impl Converter {
+  /// Converter for the configured currency, falling back to USD if it has no rates.
+  pub fn load() -> Converter {
+    Converter::from_settings(&load_settings())
+  }
+
+  pub fn from_settings(settings: &CurrencySettings) -> Converter {
+    let mut rates: Vec<(NaiveDate, f64)> = settings
+      .rates
+      .iter()
+      .filter(|r| r.currency == settings.currency && r.rate.is_finite() && r.rate > 0.0)
+      .map(|r| (r.date, r.rate))
+      .collect();
+    rates.sort_by_key(|(date, _)| *date);
+    if settings.currency == BASE_CURRENCY || rates.is_empty() {
+      return Converter::usd();
+    }
+    Converter { currency: settings.currency.clone(), rates }
+  }
+
+  pub fn usd() -> Converter {
+    Converter { currency: BASE_CURRENCY.to_string(), rates: Vec::new() }
+  }
+
+  pub fn currency(&self) -> &str {
+    &self.currency
+  }
+
+  fn is_identity(&self) -> bool {
+    self.rates.is_empty()
+  }
+
+  /// Rate in effect on `date`: the latest one on or before it, or the earliest
+  /// known rate for older dates. Undated amounts use the latest rate.
+  fn rate_on(&self, date: Option<NaiveDate>) -> f64 {
+    let Some(date) = date else {
+      return self.rates.last().map_or(1.0, |(_, r)| *r);
+    };
+    let idx = self.rates.partition_point(|(d, _)| *d <= date);
+    let (_, rate) = self.rates[idx.saturating_sub(1)];
+    rate
+  }
+
+  pub fn convert(&self, usd: f64, date: Option<NaiveDate>) -> f64 {
+    if self.is_identity() {
+      usd
+    } else {
+      usd * self.rate_on(date)
+    }
+  }
+
+  /// Entries with `cost` in the display currency, converted at each entry's local date.
+  pub fn entries<'a>(&self, entries: &'a [UsageEntry]) -> Cow<'a, [UsageEntry]> {
+    if self.is_identity() {
+      return Cow::Borrowed(entries);
+    }
+    Cow::Owned(
+      entries
+        .iter()
+        .map(|e| {
+          let mut e = e.clone();
+          e.cost = self.convert(e.cost, e.local_time().map(|t| t.date_naive()));
+          e
+        })
+        .collect(),
+    )
+  }
+
+  fn symbol(&self) -> Option<&'static str> {
+    match self.currency.as_str() {
+      "USD" => Some("$"),
+      "EUR" => Some("€"),
+      "GBP" => Some("£"),
+      "CNY" | "JPY" => Some("¥"),
+      _ => None,
+    }
+  }
+
+  pub fn display(&self) -> DisplayCurrency {
+    DisplayCurrency { code: self.currency.clone(), symbol: self.symbol() }
+  }
+
+  /// Formats an amount already in the display currency, e.g. `€12.34` or `12.34 CHF`.
+  pub fn format(&self, amount: f64) -> String {
+    match self.symbol() {
+      Some(symbol) => format!("{symbol}{amount:.2}"),
+      None => format!("{amount:.2} {}", self.currency),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::{Local, TimeZone};
+
+  fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+  }
+
+  fn eur() -> Converter {
+    let rate = |currency: &str, day: u32, rate: f64| ExchangeRate {
+      currency: currency.to_string(),
+      date: date(2025, 3, day),
+      rate,
+    };
+    Converter::from_settings(&CurrencySettings {
+      currency: "EUR".to_string(),
+      rates: vec![rate("EUR", 20, 0.8), rate("EUR", 10, 0.9), rate("GBP", 15, 0.7), rate("EUR", 15, -1.0)],
+    })
+  }
+
+  #[test]
+  fn uses_the_latest_rate_on_or_before_the_date() {
+    let c = eur();
+    assert_eq!(c.rate_on(Some(date(2025, 3, 10))), 0.9);
+    assert_eq!(c.rate_on(Some(date(2025, 3, 19))), 0.9);
+    assert_eq!(c.rate_on(Some(date(2025, 3, 20))), 0.8);
+    assert_eq!(c.rate_on(Some(date(2026, 1, 1))), 0.8);
+    // Before the first rate the earliest one applies; undated amounts use the latest.
+    assert_eq!(c.rate_on(Some(date(2024, 1, 1))), 0.9);
+    assert_eq!(c.rate_on(None), 0.8);
+  }
+
+  #[test]
+  fn converts_entries_at_their_local_date() {
+    let at = |day: u32| Local.with_ymd_and_hms(2025, 3, day, 12, 0, 0).unwrap().to_rfc3339();
+    let entries: Vec<UsageEntry> = [at(12), at(21)]
+      .into_iter()
+      .map(|timestamp| UsageEntry { timestamp, cost: 10.0, ..Default::default() })
+      .collect();
+    let costs: Vec<f64> = eur().entries(&entries).iter().map(|e| e.cost).collect();
+    assert_eq!(costs, vec![9.0, 8.0]);
+    assert_eq!(eur().format(9.0), "€9.00");
+  }
+
+  #[test]
+  fn falls_back_to_usd_without_rates() {
+    let c = Converter::from_settings(&CurrencySettings { currency: "CHF".to_string(), rates: Vec::new() });
+    assert_eq!(c.currency(), "USD");
+    assert_eq!(c.convert(2.5, Some(date(2025, 3, 1))), 2.5);
+  }
+}
@@ -0,0 +1,362 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+use crate::sources::{for_each_new_line, was_truncated, FileState, TokenCounters, UsageSource, BUILTIN_TOOLS};
+use crate::{
+  file_mtime_rfc3339, normalize_timestamp, read_config, total_tokens_with_cache,
+  value_f64, value_u64, write_config, ServiceTier, UsageEntry,
+};
+
+const CUSTOM_SOURCES_FILE: &str = "custom_sources.json";
+
+/// A user-defined JSONL log source. Field locations are JSON pointers
+/// (e.g. `/usage/input_tokens`) evaluated against each line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomSourceDef {
+  /// Tool name shown in the dashboard.
+  pub tool: String,
+  /// Base directory; a leading `~` expands to the home directory.
+  pub root: String,
+  /// Glob patterns relative to `root`, e.g. `logs/**/*.jsonl`.
+  pub patterns: Vec<String>,
+  #[serde(default)]
+  pub timestamp: Option<String>,
+  #[serde(default)]
+  pub model: Option<String>,
+  /// Model used when `model` is missing from a line.
+  #[serde(default)]
+  pub default_model: Option<String>,
+  #[serde(default)]
+  pub input: Option<String>,
+  #[serde(default)]
+  pub output: Option<String>,
+  #[serde(default)]
+  pub cache_read: Option<String>,
+  #[serde(default)]
+  pub cache_write: Option<String>,
+  #[serde(default)]
+  pub total: Option<String>,
+  /// Working directory or project name the line belongs to.
+  #[serde(default)]
+  pub project: Option<String>,
+  /// Service tier (`priority`, `flex` or `batch`); other values are priced as standard.
+  #[serde(default)]
+  pub service_tier: Option<String>,
+  /// Logged cost in USD; when absent or zero the cost is estimated from tokens.
+  #[serde(default)]
+  pub cost: Option<String>,
+  /// Token fields are running totals per file rather than per-request deltas.
+  #[serde(default)]
+  pub cumulative: bool,
+  /// The input count already includes cache reads (OpenAI/Gemini style).
+  #[serde(default)]
+  pub input_includes_cache_read: bool,
+}
+
+impl CustomSourceDef {
+  fn validate(&self) -> Result<(), String> {
+    let tool = self.tool.trim();
+    if tool.is_empty() {
+      return Err("custom source is missing a tool name".to_string());
+    }
+    if BUILTIN_TOOLS.iter().any(|b| b.eq_ignore_ascii_case(tool)) {
+      return Err(format!("'{tool}' is reserved for a built-in source"));
+    }
+    if self.root.trim().is_empty() || self.patterns.is_empty() {
+      return Err(format!("'{tool}' needs a root and at least one file pattern"));
+    }
+    let pointers = [
+      &self.timestamp,
+      &self.model,
+      &self.input,
+      &self.output,
+      &self.cache_read,
+      &self.cache_write,
+      &self.total,
+      &self.cost,
+      &self.project,
+      &self.service_tier,
+    ];
+    for ptr in pointers.into_iter().flatten() {
+      if !ptr.is_empty() && !ptr.starts_with('/') {
+        return Err(format!("'{tool}': '{ptr}' is not a JSON pointer (must start with '/')"));
+      }
+    }
+    if self.input.is_none() && self.output.is_none() && self.total.is_none() {
+      return Err(format!("'{tool}' maps none of input, output or total"));
+    }
+    Ok(())
+  }
+}
+
+/// Loads valid definitions, skipping (and logging) any that fail validation.
+pub fn load_custom_sources() -> Vec<CustomSourceDef> {
+  let defs: Vec<CustomSourceDef> = read_config(CUSTOM_SOURCES_FILE).unwrap_or_default();
+  defs
+    .into_iter()
+    .filter(|d| match d.validate() {
+      Ok(()) => true,
+      Err(e) => {
+        log::warn!("Ignoring custom source: {e}");
+        false
+      }
+    })
+    .collect()
+}
+
+pub fn save_custom_sources(defs: &[CustomSourceDef]) -> Result<(), String> {
+  for def in defs {
+    def.validate()?;
+  }
+  write_config(CUSTOM_SOURCES_FILE, defs)
+}
+
+/// Generic JSONL scanner driven by a `CustomSourceDef`.
+pub struct CustomSource {
+  def: CustomSourceDef,
+}
+
+impl CustomSource {
+  pub fn new(def: CustomSourceDef) -> Self {
+    Self { def }
+  }
+
+  fn field<'a>(v: &'a Value, ptr: &Option<String>) -> Option<&'a Value> {
+    v.pointer(ptr.as_deref()?)
+  }
+}
+
+impl UsageSource for CustomSource {
+  fn tool(&self) -> &str {
+    self.def.tool.trim()
+  }
+
+  fn roots(&self, home: &str) -> Vec<String> {
+    let root = self.def.root.trim().replace('\\', "/");
+    let root = match root.strip_prefix('~') {
+      Some(rest) => format!("{home}{rest}"),
+      None => root,
+    };
+    vec![root]
+  }
+
+  fn file_patterns(&self) -> Vec<String> {
+    self.def.patterns.clone()
+  }
+
+  fn scan_file(&self, path: &Path, state: &mut FileState) -> Vec<UsageEntry> {
+    let def = &self.def;
+    let fallback_ts = file_mtime_rfc3339(path).unwrap_or_default();
+    let mut out = Vec::new();
+
+    let mut prev_totals = if was_truncated(path, state) { None } else { state.totals };
+
+    for_each_new_line(path, state, |v| {
+      let reading = TokenCounters {
+        input: value_u64(Self::field(v, &def.input)),
+        output: value_u64(Self::field(v, &def.output)),
+        cache_read: value_u64(Self::field(v, &def.cache_read)),
+        cache_write: value_u64(Self::field(v, &def.cache_write)),
+        total: value_u64(Self::field(v, &def.total)),
+      };
+      let logged_cost = value_f64(Self::field(v, &def.cost));
+
+      let tokens = if def.cumulative {
+        if reading == TokenCounters::default() {
+          return;
+        }
+        let delta = reading.since(prev_totals.as_ref());
+        prev_totals = Some(reading);
+        delta
+      } else {
+        reading
+      };
+
+      if tokens == TokenCounters::default() && logged_cost == 0.0 {
+        return;
+      }
+
+      let total_tokens = if tokens.total > 0 {
+        tokens.total
+      } else if def.input_includes_cache_read {
+        tokens.input.saturating_add(tokens.output).saturating_add(tokens.cache_write)
+      } else {
+        total_tokens_with_cache(tokens.input, tokens.output, tokens.cache_read, tokens.cache_write)
+      };
+
+      let timestamp = normalize_timestamp(Self::field(v, &def.timestamp))
+        .unwrap_or_else(|| fallback_ts.clone());
+      let model = Self::field(v, &def.model)
+        .and_then(|m| m.as_str())
+        .map(|m| m.trim())
+        .filter(|m| !m.is_empty())
+        .or(def.default_model.as_deref())
+        .unwrap_or("unknown")
+        .to_string();
+
+      let service_tier = ServiceTier::from_value(Self::field(v, &def.service_tier));
+      out.push(UsageEntry {
+        timestamp,
+        tool: def.tool.trim().to_string(),
+        model,
+        input_tokens: tokens.input,
+        output_tokens: tokens.output,
+        cache_read_tokens: tokens.cache_read,
+        cache_write_tokens: tokens.cache_write,
+        total_tokens,
+        cost: logged_cost,
+        logged_cost: (logged_cost != 0.0).then_some(logged_cost),
+        provider: None,
+        service_tier,
+        session_id: None,
+        sidechain: false,
+        agent_id: None,
+        tool_calls: Vec::new(),
+        web_search_requests: 0,
+        project: Self::field(v, &def.project)
+          .and_then(|p| p.as_str())
+          .map(|p| p.trim())
+          .filter(|p| !p.is_empty())
+          .map(str::to_string),
+        machine_id: None,
+      });
+    });
+
+    state.totals = prev_totals;
+    out
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use std::io::Write;
+  use std::path::PathBuf;
+
+  fn temp_log(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("token-viewer-{}-custom-{name}.jsonl", std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+  }
+
+  fn append(path: &Path, lines: &[&str]) {
+    let mut f = fs::OpenOptions::new().create(true).append(true).open(path).unwrap();
+    for line in lines {
+      writeln!(f, "{line}").unwrap();
+    }
+  }
+
+  fn def() -> CustomSourceDef {
+    let ptr = |p: &str| Some(p.to_string());
+    CustomSourceDef {
+      tool: " Aider ".to_string(),
+      root: "~/.aider".to_string(),
+      patterns: vec!["**/*.jsonl".to_string()],
+      timestamp: ptr("/ts"),
+      model: ptr("/model"),
+      default_model: None,
+      input: ptr("/usage/in"),
+      output: ptr("/usage/out"),
+      cache_read: ptr("/usage/cached"),
+      cache_write: None,
+      total: None,
+      project: ptr("/cwd"),
+      service_tier: None,
+      cost: ptr("/cost"),
+      cumulative: false,
+      input_includes_cache_read: false,
+    }
+  }
+
+  fn tokens(entries: &[UsageEntry]) -> Vec<(u64, u64, u64, u64)> {
+    entries
+      .iter()
+      .map(|e| (e.input_tokens, e.output_tokens, e.cache_read_tokens, e.total_tokens))
+      .collect()
+  }
+
+  #[test]
+  fn reads_per_request_counts() {
+    let path = temp_log("per-request");
+    append(&path, &[
+      r#"{"ts":"2025-01-01T10:00:00Z","model":"gpt-5","cwd":"/work/app","usage":{"in":100,"out":10,"cached":40},"cost":0.02}"#,
+      r#"{"ts":"2025-01-01T10:01:00Z","model":"gpt-5","usage":{"in":50,"out":5}}"#,
+    ]);
+
+    let entries = CustomSource::new(def()).scan_file(&path, &mut FileState::default());
+    assert_eq!(tokens(&entries), vec![(100, 10, 40, 150), (50, 5, 0, 55)]);
+    assert_eq!(entries[0].tool, "Aider");
+    assert_eq!(entries[0].project.as_deref(), Some("/work/app"));
+    assert_eq!((entries[0].cost, entries[0].logged_cost), (0.02, Some(0.02)));
+    assert_eq!((entries[1].cost, entries[1].logged_cost), (0.0, None));
+    let _ = fs::remove_file(&path);
+  }
+
+  #[test]
+  fn leaves_cache_reads_out_of_the_total_when_input_includes_them() {
+    let path = temp_log("includes-cache");
+    append(&path, &[r#"{"model":"gpt-5","usage":{"in":100,"out":10,"cached":40}}"#]);
+
+    let source = CustomSource::new(CustomSourceDef { input_includes_cache_read: true, ..def() });
+    let entries = source.scan_file(&path, &mut FileState::default());
+    assert_eq!(tokens(&entries), vec![(100, 10, 40, 110)]);
+    let _ = fs::remove_file(&path);
+  }
+
+  #[test]
+  fn counts_cumulative_totals_as_deltas_across_scans() {
+    let path = temp_log("cumulative");
+    let line = |i: u64, o: u64| format!(r#"{{"model":"gpt-5","usage":{{"in":{i},"out":{o}}}}}"#);
+    append(&path, &[&line(100, 10), &line(250, 30), r#"{"event":"no usage"}"#]);
+
+    let source = CustomSource::new(CustomSourceDef { cumulative: true, ..def() });
+    let mut state = FileState::default();
+    let first = source.scan_file(&path, &mut state);
+    assert_eq!(tokens(&first), vec![(100, 10, 0, 110), (150, 20, 0, 170)]);
+
+    append(&path, &[&line(400, 45)]);
+    let second = source.scan_file(&path, &mut state);
+    assert_eq!(tokens(&second), vec![(150, 15, 0, 165)]);
+    assert!(source.scan_file(&path, &mut state).is_empty());
+    let _ = fs::remove_file(&path);
+  }
+
+  #[test]
+  fn restarts_cumulative_totals_when_the_file_is_truncated() {
+    let path = temp_log("truncated");
+    let line = |i: u64| format!(r#"{{"model":"gpt-5","usage":{{"in":{i},"out":1}}}}"#);
+    append(&path, &[&line(1000), &line(2000)]);
+
+    let source = CustomSource::new(CustomSourceDef { cumulative: true, ..def() });
+    let mut state = FileState::default();
+    source.scan_file(&path, &mut state);
+
+    // A new run rewrote the log with smaller running totals.
+    fs::write(&path, format!("{}\n", line(30))).unwrap();
+    assert_eq!(tokens(&source.scan_file(&path, &mut state)), vec![(30, 1, 0, 31)]);
+    let _ = fs::remove_file(&path);
+  }
+
+  #[test]
+  fn falls_back_when_pointers_are_missing() {
+    let path = temp_log("missing");
+    append(&path, &[
+      r#"{"usage":{"in":10}}"#,
+      r#"{"model":"  ","usage":{"out":5}}"#,
+      r#"{"ts":"2025-01-01T10:00:00Z","model":"gpt-5"}"#,
+      "not json",
+    ]);
+
+    let entries = CustomSource::new(def()).scan_file(&path, &mut FileState::default());
+    assert_eq!(tokens(&entries), vec![(10, 0, 0, 10), (0, 5, 0, 5)]);
+    assert!(entries.iter().all(|e| e.model == "unknown" && !e.timestamp.is_empty() && e.project.is_none()));
+
+    let with_default = CustomSourceDef { default_model: Some("local-llm".to_string()), model: None, ..def() };
+    let entries = CustomSource::new(with_default).scan_file(&path, &mut FileState::default());
+    assert!(entries.iter().all(|e| e.model == "local-llm"));
+    let _ = fs::remove_file(&path);
+  }
+}
@@ -0,0 +1,309 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::budgets::{local_midnight, Budget, BudgetPeriod};
+use crate::currency::Converter;
+use crate::UsageEntry;
+
+/// Complete days before today used to fit the daily rate.
+const HISTORY_DAYS: i64 = 28;
+/// Weekday factors need at least two of each weekday to mean anything.
+const MIN_SEASONAL_DAYS: usize = 14;
+/// Two-sided 80% normal interval.
+const BAND_Z: f64 = 1.2816;
+pub const BAND_CONFIDENCE: f64 = 0.8;
+
+/// Daily level, weekday factors and residual spread fitted to a history.
+struct Fit {
+  level: f64,
+  /// Multiplier per weekday, Monday first.
+  weekday: [f64; 7],
+  sigma: f64,
+}
+
+impl Fit {
+  fn predict(&self, date: NaiveDate) -> f64 {
+    self.level * self.weekday[date.weekday().num_days_from_monday() as usize]
+  }
+}
+
+/// Fits a linearly weighted moving average (most recent day weighs most) to
+/// the deseasonalized daily values, oldest first.
+fn fit(history: &[(NaiveDate, f64)]) -> Fit {
+  let mut weekday = [1.0; 7];
+  if history.len() >= MIN_SEASONAL_DAYS {
+    let mean = history.iter().map(|(_, y)| y).sum::<f64>() / history.len() as f64;
+    if mean > 0.0 {
+      let mut sums = [(0.0, 0usize); 7];
+      for (date, y) in history {
+        let slot = &mut sums[date.weekday().num_days_from_monday() as usize];
+        slot.0 += y;
+        slot.1 += 1;
+      }
+      for (factor, (sum, count)) in weekday.iter_mut().zip(sums) {
+        if count > 0 {
+          *factor = sum / count as f64 / mean;
+        }
+      }
+    }
+  }
+
+  let (mut weighted, mut weights) = (0.0, 0.0);
+  for (i, (date, y)) in history.iter().enumerate() {
+    let factor = weekday[date.weekday().num_days_from_monday() as usize];
+    // A weekday that never has usage says nothing about the level.
+    if factor > 0.0 {
+      let w = (i + 1) as f64;
+      weighted += w * y / factor;
+      weights += w;
+    }
+  }
+  let level = if weights > 0.0 { weighted / weights } else { 0.0 };
+
+  let mut fit = Fit { level, weekday, sigma: 0.0 };
+  if history.len() > 1 {
+    let sq: f64 = history.iter().map(|(date, y)| (y - fit.predict(*date)).powi(2)).sum();
+    fit.sigma = (sq / (history.len() - 1) as f64).sqrt();
+  }
+  fit
+}
+
+/// Month-end estimate for one value (cost or tokens): what has happened so far
+/// plus the fitted remainder, with an 80% band around it.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Estimate {
+  pub month_to_date: f64,
+  /// Fitted typical day, before weekday adjustment.
+  pub daily_rate: f64,
+  pub projected: f64,
+  pub low: f64,
+  pub high: f64,
+}
+
+/// Projects from `history` (complete days, oldest first) to the end of the
+/// month, given the total so far and how much of today is left.
+fn estimate(
+  history: &[(NaiveDate, f64)],
+  month_to_date: f64,
+  today: NaiveDate,
+  month_end: NaiveDate,
+  today_left: f64,
+) -> Estimate {
+  let fit = fit(history);
+  let mut remaining = fit.predict(today) * today_left;
+  let mut variance = today_left * today_left;
+  let mut date = today + Duration::days(1);
+  while date < month_end {
+    remaining += fit.predict(date);
+    variance += 1.0;
+    date += Duration::days(1);
+  }
+  let projected = month_to_date + remaining;
+  let spread = BAND_Z * fit.sigma * variance.sqrt();
+  Estimate {
+    month_to_date,
+    daily_rate: fit.level,
+    projected,
+    low: (projected - spread).max(month_to_date),
+    high: projected + spread,
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Projection {
+  /// Tool name, or `all`.
+  pub key: String,
+  pub cost: Estimate,
+  pub tokens: Estimate,
+}
+
+/// Month-end projection for a monthly budget, in USD like the budget itself.
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetForecast {
+  pub budget_id: String,
+  pub limit: f64,
+  pub cost: Estimate,
+  /// The projection exceeds the limit.
+  pub likely_to_exceed: bool,
+  /// Even the low end of the band exceeds the limit.
+  pub certain_to_exceed: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Forecast {
+  pub as_of: NaiveDate,
+  pub month_start: NaiveDate,
+  /// Exclusive.
+  pub month_end: NaiveDate,
+  /// Complete days of history the fit used.
+  pub history_days: i64,
+  pub confidence: f64,
+  /// Currency of the projection costs; budget forecasts stay in USD.
+  pub currency: String,
+  pub overall: Projection,
+  pub tools: Vec<Projection>,
+  pub budgets: Vec<BudgetForecast>,
+}
+
+/// Daily cost and token totals for one series, keyed by local date.
+#[derive(Default)]
+struct Daily(BTreeMap<NaiveDate, (f64, f64)>);
+
+impl Daily {
+  fn add(&mut self, date: NaiveDate, e: &UsageEntry) {
+    let day = self.0.entry(date).or_default();
+    day.0 += e.cost;
+    day.1 += e.total_tokens as f64;
+  }
+
+  /// Cost and token estimates; history starts at the first day with usage so
+  /// a recent install is not averaged down by empty days.
+  fn project(
+    &self,
+    today: NaiveDate,
+    month_start: NaiveDate,
+    month_end: NaiveDate,
+    today_left: f64,
+  ) -> (Estimate, Estimate) {
+    let first = self.0.keys().next().copied().unwrap_or(today);
+    let start = first.max(today - Duration::days(HISTORY_DAYS));
+    let mut cost = Vec::new();
+    let mut tokens = Vec::new();
+    let mut date = start;
+    while date < today {
+      let (c, t) = self.0.get(&date).copied().unwrap_or_default();
+      cost.push((date, c));
+      tokens.push((date, t));
+      date += Duration::days(1);
+    }
+    let (mtd_cost, mtd_tokens) = self
+      .0
+      .range(month_start..=today)
+      .fold((0.0, 0.0), |(c, t), (_, (dc, dt))| (c + dc, t + dt));
+    if cost.is_empty() {
+      // No complete day yet: extrapolate today's pace, counting at least an hour as elapsed.
+      let elapsed = (1.0 - today_left).max(1.0 / 24.0);
+      let (c, t) = self.0.get(&today).copied().unwrap_or_default();
+      cost.push((today, c / elapsed));
+      tokens.push((today, t / elapsed));
+    }
+    (
+      estimate(&cost, mtd_cost, today, month_end, today_left),
+      estimate(&tokens, mtd_tokens, today, month_end, today_left),
+    )
+  }
+}
+
+/// Projects this month's cost and tokens overall, per tool and per monthly
+/// budget from the last four weeks of daily totals, using weekday seasonality.
+pub fn forecast(
+  entries: &[UsageEntry],
+  budgets: &[Budget],
+  now: DateTime<Local>,
+  converter: &Converter,
+) -> Forecast {
+  let today = now.date_naive();
+  let (month_start, month_end) = {
+    let (start, end) = BudgetPeriod::Monthly.bounds(now);
+    (start.date_naive(), end.date_naive())
+  };
+  let day_start = local_midnight(today);
+  let elapsed = (now - day_start).num_seconds() as f64 / Duration::days(1).num_seconds() as f64;
+  let today_left = (1.0 - elapsed).clamp(0.0, 1.0);
+  let history_from = today - Duration::days(HISTORY_DAYS);
+
+  let converted = converter.entries(entries);
+  let mut overall = Daily::default();
+  let mut tools: HashMap<&str, Daily> = HashMap::new();
+  for e in converted.iter() {
+    let Some(date) = e.local_time().map(|t| t.date_naive()) else {
+      continue;
+    };
+    if date > today {
+      continue;
+    }
+    overall.add(date, e);
+    tools.entry(e.tool.as_str()).or_default().add(date, e);
+  }
+
+  let projection = |key: &str, daily: &Daily| {
+    let (cost, tokens) = daily.project(today, month_start, month_end, today_left);
+    Projection { key: key.to_string(), cost, tokens }
+  };
+  let mut tool_projections: Vec<Projection> =
+    tools.iter().map(|(tool, daily)| projection(tool, daily)).collect();
+  tool_projections.sort_by(|a, b| b.cost.projected.total_cmp(&a.cost.projected));
+
+  let budget_forecasts = budgets
+    .iter()
+    .filter(|b| b.period == BudgetPeriod::Monthly)
+    .map(|budget| {
+      let mut daily = Daily::default();
+      for e in entries.iter().filter(|e| budget.matches(e)) {
+        if let Some(date) = e.local_time().map(|t| t.date_naive()) {
+          if date <= today {
+            daily.add(date, e);
+          }
+        }
+      }
+      let (cost, _) = daily.project(today, month_start, month_end, today_left);
+      BudgetForecast {
+        budget_id: budget.id.clone(),
+        limit: budget.limit,
+        cost,
+        likely_to_exceed: cost.projected > budget.limit,
+        certain_to_exceed: cost.low > budget.limit,
+      }
+    })
+    .collect();
+
+  let history_start = overall.0.keys().next().copied().unwrap_or(today).max(history_from);
+  Forecast {
+    as_of: today,
+    month_start,
+    month_end,
+    history_days: (today - history_start).num_days().max(0),
+    confidence: BAND_CONFIDENCE,
+    currency: converter.currency().to_string(),
+    overall: projection("all", &overall),
+    tools: tool_projections,
+    budgets: budget_forecasts,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn date(d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2025, 3, d).unwrap()
+  }
+
+  #[test]
+  fn steady_usage_projects_the_same_rate_with_no_band() {
+    // 2025-03-20 with 19 complete days of $10/day behind it and $4 so far today.
+    let history: Vec<_> = (1..20).map(|d| (date(d), 10.0)).collect();
+    let month_to_date = 19.0 * 10.0 + 4.0;
+    let month_end = NaiveDate::from_ymd_opt(2025, 4, 1).unwrap();
+    let e = estimate(&history, month_to_date, date(20), month_end, 0.5);
+    assert!((e.daily_rate - 10.0).abs() < 1e-9);
+    // Half of today plus 11 more days.
+    assert!((e.projected - (month_to_date + 5.0 + 110.0)).abs() < 1e-9);
+    assert!((e.high - e.low).abs() < 1e-9);
+  }
+
+  #[test]
+  fn weekday_seasonality_follows_the_weekly_pattern() {
+    // Weekdays cost 10, weekends nothing; 2025-03-03 is a Monday.
+    let history: Vec<_> = (3..31)
+      .map(|d| {
+        let day = date(d);
+        (day, if day.weekday().num_days_from_monday() < 5 { 10.0 } else { 0.0 })
+      })
+      .collect();
+    let fit = fit(&history);
+    assert!((fit.predict(date(31)) - 10.0).abs() < 1e-9); // Monday
+    assert_eq!(fit.predict(date(29)), 0.0); // Saturday
+  }
+}
@@ -0,0 +1,250 @@
+use chrono::{Duration, Local, NaiveDate};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::aggregate::{self, AggregateRow, EntryFilter, GroupBy, Totals};
+use crate::cache;
+use crate::currency::Converter;
+use crate::{PricingTable, UsageEntry};
+
+/// Rows shown in the top models / top projects tables.
+const TOP_N: usize = 10;
+
+const STYLE: &str = "
+body { font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; color: #1f2933;
+  background: #f5f7fa; margin: 0; padding: 32px; }
+main { max-width: 960px; margin: 0 auto; }
+h1 { font-size: 24px; margin: 0 0 4px; }
+h2 { font-size: 18px; margin: 32px 0 12px; }
+.muted { color: #7b8794; font-size: 13px; }
+.cards { display: grid; grid-template-columns: repeat(auto-fit, minmax(170px, 1fr)); gap: 12px; margin-top: 20px; }
+.card { background: #fff; border-radius: 8px; padding: 14px 16px; box-shadow: 0 1px 2px rgba(0,0,0,.08); }
+.card .label { color: #7b8794; font-size: 12px; text-transform: uppercase; letter-spacing: .04em; }
+.card .value { font-size: 22px; font-weight: 600; margin-top: 4px; }
+.panel { background: #fff; border-radius: 8px; padding: 16px; box-shadow: 0 1px 2px rgba(0,0,0,.08); }
+table { width: 100%; border-collapse: collapse; font-size: 13px; }
+th, td { padding: 6px 8px; border-bottom: 1px solid #e4e7eb; text-align: right; }
+th:first-child, td:first-child { text-align: left; word-break: break-all; }
+th { color: #7b8794; font-weight: 500; }
+.bar { background: #3b82f6; height: 8px; border-radius: 4px; }
+svg text { font-size: 10px; fill: #7b8794; }
+@media print { body { background: #fff; padding: 0; } .card, .panel { box-shadow: none; border: 1px solid #e4e7eb; } }
+";
+
+fn escape(s: &str) -> String {
+  s.replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+fn format_int(n: u64) -> String {
+  let digits = n.to_string();
+  let mut out = String::new();
+  for (i, c) in digits.chars().enumerate() {
+    if i > 0 && (digits.len() - i) % 3 == 0 {
+      out.push(',');
+    }
+    out.push(c);
+  }
+  out
+}
+
+/// Daily cost as an SVG bar chart, one bar per day including days without usage.
+fn daily_chart(days: &BTreeMap<NaiveDate, f64>, converter: &Converter) -> String {
+  let (Some(first), Some(last)) = (days.keys().next(), days.keys().next_back()) else {
+    return "<p class=\"muted\">No usage in this period.</p>".to_string();
+  };
+  let count = (*last - *first).num_days() as usize + 1;
+  let max = days.values().copied().fold(0.0, f64::max).max(f64::EPSILON);
+
+  let (width, height, top, bottom) = (900.0, 200.0, 10.0, 20.0);
+  let plot = height - top - bottom;
+  let step = width / count as f64;
+  let bar = (step * 0.8).max(1.0);
+
+  let mut svg = format!(
+    "<svg viewBox=\"0 0 {width} {height}\" width=\"100%\" role=\"img\" aria-label=\"Daily cost\">"
+  );
+  let _ = write!(svg, "<line x1=\"0\" y1=\"{0}\" x2=\"{width}\" y2=\"{0}\" stroke=\"#e4e7eb\"/>", top + plot);
+  let label_every = count.div_ceil(10);
+  for i in 0..count {
+    let date = *first + Duration::days(i as i64);
+    let cost = days.get(&date).copied().unwrap_or(0.0);
+    let h = cost / max * plot;
+    let x = i as f64 * step + (step - bar) / 2.0;
+    let _ = write!(
+      svg,
+      "<rect x=\"{x:.1}\" y=\"{:.1}\" width=\"{bar:.1}\" height=\"{h:.1}\" fill=\"#3b82f6\" rx=\"1\">\
+       <title>{date}: {}</title></rect>",
+      top + plot - h,
+      converter.format(cost)
+    );
+    if i % label_every == 0 {
+      let _ = write!(
+        svg,
+        "<text x=\"{:.1}\" y=\"{height}\" text-anchor=\"middle\">{}</text>",
+        x + bar / 2.0,
+        date.format("%m-%d")
+      );
+    }
+  }
+  let _ = write!(svg, "<text x=\"2\" y=\"{}\">{}</text></svg>", top + 8.0, converter.format(max));
+  svg
+}
+
+fn top_table(title: &str, rows: &[AggregateRow], total_cost: f64, converter: &Converter) -> String {
+  let mut rows: Vec<&AggregateRow> = rows.iter().collect();
+  rows.sort_by(|a, b| b.totals.cost.total_cmp(&a.totals.cost));
+  let mut out = format!(
+    "<h2>{title}</h2><div class=\"panel\"><table><tr><th>{}</th><th>Requests</th><th>Tokens</th>\
+     <th>Cost</th><th style=\"width:25%\">Share</th></tr>",
+    title.trim_start_matches("Top ")
+  );
+  for row in rows.iter().take(TOP_N) {
+    let share = if total_cost > 0.0 { row.totals.cost / total_cost * 100.0 } else { 0.0 };
+    let _ = write!(
+      out,
+      "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+       <td><div class=\"bar\" style=\"width:{share:.1}%\" title=\"{share:.1}%\"></div></td></tr>",
+      escape(&row.key),
+      format_int(row.totals.requests),
+      format_int(row.totals.total_tokens),
+      converter.format(row.totals.cost)
+    );
+  }
+  if rows.is_empty() {
+    out.push_str("<tr><td colspan=\"5\" class=\"muted\">No usage in this period.</td></tr>");
+  }
+  out.push_str("</table></div>");
+  out
+}
+
+fn card(label: &str, value: &str) -> String {
+  format!("<div class=\"card\"><div class=\"label\">{label}</div><div class=\"value\">{value}</div></div>")
+}
+
+/// Renders a self-contained HTML summary of the entries within `[since, until]`:
+/// totals, daily cost trend, top models and projects, and cache hit ratio.
+/// Charts are inline SVG and the page loads nothing else. Money is shown in
+/// the converter's currency; cache savings are priced from `pricing`.
+pub fn render(
+  entries: &[UsageEntry],
+  since: Option<NaiveDate>,
+  until: Option<NaiveDate>,
+  converter: &Converter,
+  pricing: &PricingTable,
+) -> String {
+  let entries = &*converter.entries(entries);
+  let filter = EntryFilter { since, until, ..Default::default() };
+  let matching: Vec<&UsageEntry> = entries.iter().filter(|e| filter.matches(e)).collect();
+  let totals = Totals::from_entries(matching.iter().copied());
+
+  let mut days: BTreeMap<NaiveDate, f64> = BTreeMap::new();
+  for e in &matching {
+    if let Some(t) = e.local_time() {
+      *days.entry(t.date_naive()).or_default() += e.cost;
+    }
+  }
+  // Show the whole requested period, not just the days with usage.
+  for bound in [since, until].into_iter().flatten() {
+    days.entry(bound).or_default();
+  }
+
+  let period = match (since, until) {
+    (Some(s), Some(u)) => format!("{s} to {u}"),
+    (Some(s), None) => format!("Since {s}"),
+    (None, Some(u)) => format!("Until {u}"),
+    (None, None) => match (days.keys().next(), days.keys().next_back()) {
+      (Some(s), Some(u)) => format!("{s} to {u}"),
+      _ => "All time".to_string(),
+    },
+  };
+  let cache = cache::overall(matching.iter().copied(), converter, pricing);
+  let hit_ratio = cache
+    .hit_ratio
+    .map(|r| format!("{:.1}%", r * 100.0))
+    .unwrap_or_else(|| "n/a".to_string());
+  let models = aggregate::aggregate(entries, &filter, GroupBy::Model);
+  let projects = aggregate::aggregate(entries, &filter, GroupBy::Project);
+
+  let mut html = String::new();
+  let _ = write!(
+    html,
+    "<!DOCTYPE html><html lang=\"en\"><head><meta charset=\"utf-8\">\
+     <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+     <title>Token usage report: {period}</title><style>{STYLE}</style></head><body><main>\
+     <h1>Token usage report</h1><div class=\"muted\">{period} &middot; generated {}</div>",
+    Local::now().format("%Y-%m-%d %H:%M")
+  );
+
+  html.push_str("<div class=\"cards\">");
+  html.push_str(&card("Cost", &converter.format(totals.cost)));
+  html.push_str(&card("Requests", &format_int(totals.requests)));
+  html.push_str(&card("Total tokens", &format_int(totals.total_tokens)));
+  html.push_str(&card("Input / output", &format!(
+    "{} / {}",
+    format_int(totals.input_tokens),
+    format_int(totals.output_tokens)
+  )));
+  html.push_str(&card("Cache read / write", &format!(
+    "{} / {}",
+    format_int(totals.cache_read_tokens),
+    format_int(totals.cache_write_tokens)
+  )));
+  html.push_str(&card("Cache hit ratio", &hit_ratio));
+  html.push_str(&card("Cache savings", &converter.format(cache.net_savings)));
+  html.push_str("</div>");
+
+  let _ = write!(html, "<h2>Daily cost</h2><div class=\"panel\">{}</div>", daily_chart(&days, converter));
+  html.push_str(&top_table("Top models", &models, totals.cost, converter));
+  html.push_str(&top_table("Top projects", &projects, totals.cost, converter));
+  html.push_str("</main></body></html>\n");
+  html
+}
+
+pub fn write(
+  path: &Path,
+  entries: &[UsageEntry],
+  since: Option<NaiveDate>,
+  until: Option<NaiveDate>,
+  converter: &Converter,
+  pricing: &PricingTable,
+) -> Result<(), String> {
+  std::fs::write(path, render(entries, since, until, converter, pricing)).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashMap;
+
+  #[test]
+  fn escapes_model_and_project_names() {
+    let entry = UsageEntry {
+      timestamp: "2025-03-10T12:00:00Z".to_string(),
+      tool: "Claude".to_string(),
+      model: "<script>alert(\"model\")</script>".to_string(),
+      input_tokens: 1234,
+      total_tokens: 1234,
+      cost: 1.5,
+      project: Some("/src/a&b <dev>".to_string()),
+      ..Default::default()
+    };
+    let html = render(&[entry], None, None, &Converter::usd(), &PricingTable::new(HashMap::new()));
+
+    assert!(!html.contains("<script>"));
+    assert!(html.contains("<td>&lt;script&gt;alert(&quot;model&quot;)&lt;/script&gt;</td>"));
+    assert!(html.contains("<td>/src/a&amp;b &lt;dev&gt;</td>"));
+    assert!(html.contains("<td>1,234</td>"));
+  }
+
+  #[test]
+  fn groups_thousands() {
+    assert_eq!(format_int(0), "0");
+    assert_eq!(format_int(999), "999");
+    assert_eq!(format_int(1_000), "1,000");
+    assert_eq!(format_int(12_345_678), "12,345,678");
+  }
+}
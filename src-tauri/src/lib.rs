@@ -1,6 +1,1327 @@
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use glob::glob;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use std::collections::HashMap;
+use once_cell::sync::Lazy;
+
+mod aggregate;
+mod anomalies;
+mod api_server;
+mod budgets;
+mod cache;
+mod bundle;
+pub mod cli;
+mod cost_mode;
+mod currency;
+mod custom_sources;
+mod forecast;
+mod html_report;
+mod mcp;
+mod metrics;
+mod otlp;
+mod plans;
+mod providers;
+mod sessions;
+mod sources;
+mod statusline;
+mod team_report;
+mod tool_calls;
+
+use aggregate::{AggregateRow, EntryFilter, GroupBy, UsageBlock};
+use anomalies::{Anomaly, AnomalySettings};
+use api_server::ApiSettings;
+use budgets::{Budget, BudgetStatus};
+use cache::CacheMetrics;
+use bundle::ImportSummary;
+use otlp::OtlpSettings;
+use plans::{Plan, PlanPeriodValue};
+use providers::{Provider, ProviderRule};
+use sessions::{SessionPage, SessionSort};
+use tool_calls::ToolCallBreakdown;
+use cost_mode::CostMode;
+use currency::{Converter, CurrencySettings, DisplayCurrency};
+use custom_sources::CustomSourceDef;
+use forecast::Forecast;
+use sources::{find_source, registry, scan_source, FileState, UsageSource};
+
+/// OpenAI/Anthropic processing tier a request ran on. Standard requests are `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceTier {
+  Priority,
+  Flex,
+  Batch,
+}
+
+impl ServiceTier {
+  /// Reads a logged `service_tier` value; `default`, `auto`, `standard` and
+  /// unknown tiers are billed at standard rates.
+  fn from_value(value: Option<&Value>) -> Option<ServiceTier> {
+    match value?.as_str()?.trim().to_lowercase().as_str() {
+      "priority" => Some(ServiceTier::Priority),
+      "flex" => Some(ServiceTier::Flex),
+      "batch" | "batches" => Some(ServiceTier::Batch),
+      _ => None,
+    }
+  }
+}
+
+/// Per-token rates for a service tier. Zero means LiteLLM lists no tier price
+/// and the standard rate applies; a missing above-200k rate is derived from the
+/// standard one.
+#[derive(Clone, Copy, Default)]
+struct TierRates {
+  input: f64,
+  output: f64,
+  cache_read: f64,
+  input_above_200k: f64,
+  output_above_200k: f64,
+  cache_read_above_200k: f64,
+}
+
+impl TierRates {
+  fn from_json(val: &Value, suffix: &str) -> Option<TierRates> {
+    let rate = |field: &str| {
+      val
+        .get(format!("{field}_{suffix}"))
+        .and_then(|v| v.as_f64())
+        .unwrap_or(0.0)
+    };
+    let rates = TierRates {
+      input: rate("input_cost_per_token"),
+      output: rate("output_cost_per_token"),
+      cache_read: rate("cache_read_input_token_cost"),
+      input_above_200k: rate("input_cost_per_token_above_200k_tokens"),
+      output_above_200k: rate("output_cost_per_token_above_200k_tokens"),
+      cache_read_above_200k: rate("cache_read_input_token_cost_above_200k_tokens"),
+    };
+    (rates.input > 0.0 || rates.output > 0.0).then_some(rates)
+  }
+}
+
+#[derive(Clone, Copy, Default)]
+struct PricingInfo {
+  input_cost_per_token: f64,
+  output_cost_per_token: f64,
+  cache_read_cost: f64,
+  cache_write_cost: f64,
+  // Tiered pricing for 200k+ tokens (Claude models)
+  input_cost_above_200k: f64,
+  output_cost_above_200k: f64,
+  cache_read_cost_above_200k: f64,
+  cache_write_cost_above_200k: f64,
+  /// Per server-side web search; zero when LiteLLM lists no search price.
+  web_search_cost: f64,
+  priority: Option<TierRates>,
+  flex: Option<TierRates>,
+  batch: Option<TierRates>,
+}
+
+impl PricingInfo {
+  /// Rates for `tier`; anything the tier does not list stays standard. Above
+  /// 200k tokens the tier's own long-context rate applies where LiteLLM lists
+  /// one, otherwise the standard long-context rate scaled by the tier's
+  /// discount or premium.
+  fn for_tier(&self, tier: Option<ServiceTier>) -> PricingInfo {
+    fn above_200k(listed: f64, tier: f64, standard: f64, standard_above: f64) -> f64 {
+      if listed > 0.0 {
+        listed
+      } else if standard > 0.0 {
+        standard_above * tier / standard
+      } else {
+        0.0
+      }
+    }
+
+    let rates = match tier {
+      Some(ServiceTier::Priority) => self.priority,
+      Some(ServiceTier::Flex) => self.flex,
+      Some(ServiceTier::Batch) => self.batch,
+      None => None,
+    };
+    let Some(rates) = rates else {
+      return *self;
+    };
+    let mut p = *self;
+    if rates.input > 0.0 {
+      p.input_cost_per_token = rates.input;
+      p.input_cost_above_200k = above_200k(
+        rates.input_above_200k,
+        rates.input,
+        self.input_cost_per_token,
+        self.input_cost_above_200k,
+      );
+    }
+    if rates.output > 0.0 {
+      p.output_cost_per_token = rates.output;
+      p.output_cost_above_200k = above_200k(
+        rates.output_above_200k,
+        rates.output,
+        self.output_cost_per_token,
+        self.output_cost_above_200k,
+      );
+    }
+    if rates.cache_read > 0.0 {
+      p.cache_read_cost = rates.cache_read;
+      p.cache_read_cost_above_200k = above_200k(
+        rates.cache_read_above_200k,
+        rates.cache_read,
+        self.cache_read_cost,
+        self.cache_read_cost_above_200k,
+      );
+    }
+    p
+  }
+
+  /// Cost of one request at these rates, each token kind tiered at 200k.
+  fn cost(&self, input: u64, output: u64, cache_read: u64, cache_write: u64) -> f64 {
+    let input_cost = tiered_cost(input, self.input_cost_per_token, self.input_cost_above_200k);
+    let output_cost = tiered_cost(output, self.output_cost_per_token, self.output_cost_above_200k);
+    let cache_read_cost = tiered_cost(cache_read, self.cache_read_cost, self.cache_read_cost_above_200k);
+    let cache_write_cost = tiered_cost(cache_write, self.cache_write_cost, self.cache_write_cost_above_200k);
+    input_cost + output_cost + cache_read_cost + cache_write_cost
+  }
+}
+
+const PRICING_URL: &str = "https://raw.githubusercontent.com/BerriAI/litellm/main/model_prices_and_context_window.json";
+
+const PRICING_CACHE_FILE: &str = "litellm_pricing.json";
+const PRICING_CACHE_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+const PRICING_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The cached copy of the LiteLLM price table, however old.
+fn read_pricing_cache() -> Option<Value> {
+  let raw = fs::read_to_string(app_cache_dir()?.join(PRICING_CACHE_FILE)).ok()?;
+  serde_json::from_str(&raw).ok()
+}
+
+/// Returns the LiteLLM price table, using a cached copy younger than a day so
+/// short-lived CLI invocations stay fast. A stale copy is used if fetching fails.
+fn fetch_pricing_json() -> Option<Value> {
+  let cache_path = app_cache_dir().map(|d| d.join(PRICING_CACHE_FILE));
+  let cache_age = cache_path
+    .as_ref()
+    .and_then(|p| fs::metadata(p).ok()?.modified().ok()?.elapsed().ok());
+  if cache_age.is_some_and(|age| age < PRICING_CACHE_MAX_AGE) {
+    if let Some(v) = read_pricing_cache() {
+      return Some(v);
+    }
+  }
+
+  let fetched = reqwest::blocking::Client::builder()
+    .timeout(PRICING_FETCH_TIMEOUT)
+    .build()
+    .and_then(|client| client.get(PRICING_URL).send())
+    .and_then(|r| r.error_for_status())
+    .and_then(|r| r.text());
+  let raw = match fetched {
+    Ok(raw) => raw,
+    Err(e) => {
+      log::warn!("Failed to fetch LiteLLM pricing: {e}");
+      return read_pricing_cache();
+    }
+  };
+  let json: Value = match serde_json::from_str(&raw) {
+    Ok(v) => v,
+    Err(e) => {
+      log::warn!("Failed to parse LiteLLM pricing: {e}");
+      return read_pricing_cache();
+    }
+  };
+  if let Some(path) = cache_path {
+    if let Err(e) = write_atomic(&path, raw.as_bytes()) {
+      log::debug!("Failed to cache LiteLLM pricing: {e}");
+    }
+  }
+  Some(json)
+}
+
+fn load_pricing() -> HashMap<String, PricingInfo> {
+  fetch_pricing_json().map(|json| parse_pricing(&json)).unwrap_or_default()
+}
+
+fn parse_pricing(json: &Value) -> HashMap<String, PricingInfo> {
+  let obj = match json.as_object() {
+    Some(o) => o,
+    None => return HashMap::new(),
+  };
+  let mut map = HashMap::new();
+  for (key, val) in obj {
+    let input = val
+      .get("input_cost_per_token")
+      .and_then(|v| v.as_f64())
+      .unwrap_or(0.0);
+    let output = val
+      .get("output_cost_per_token")
+      .and_then(|v| v.as_f64())
+      .unwrap_or(0.0);
+    if input == 0.0 && output == 0.0 {
+      continue;
+    }
+    let cache_read = val
+      .get("cache_read_input_token_cost")
+      .and_then(|v| v.as_f64())
+      .unwrap_or(0.0);
+    let cache_write = val
+      .get("cache_creation_input_token_cost")
+      .and_then(|v| v.as_f64())
+      .unwrap_or(0.0);
+    let input_above_200k = val
+      .get("input_cost_per_token_above_200k_tokens")
+      .and_then(|v| v.as_f64())
+      .unwrap_or(0.0);
+    let output_above_200k = val
+      .get("output_cost_per_token_above_200k_tokens")
+      .and_then(|v| v.as_f64())
+      .unwrap_or(0.0);
+    let cache_read_above_200k = val
+      .get("cache_read_input_token_cost_above_200k_tokens")
+      .and_then(|v| v.as_f64())
+      .unwrap_or(0.0);
+    let cache_write_above_200k = val
+      .get("cache_creation_input_token_cost_above_200k_tokens")
+      .and_then(|v| v.as_f64())
+      .unwrap_or(0.0);
+    // Priced per search context size; agents search with the default, medium.
+    let web_search = val
+      .pointer("/search_context_cost_per_query/search_context_size_medium")
+      .and_then(|v| v.as_f64())
+      .unwrap_or(0.0);
+    map.insert(
+      key.clone(),
+      PricingInfo {
+        input_cost_per_token: input,
+        output_cost_per_token: output,
+        cache_read_cost: cache_read,
+        cache_write_cost: cache_write,
+        input_cost_above_200k: input_above_200k,
+        output_cost_above_200k: output_above_200k,
+        cache_read_cost_above_200k: cache_read_above_200k,
+        cache_write_cost_above_200k: cache_write_above_200k,
+        web_search_cost: web_search,
+        priority: TierRates::from_json(val, "priority"),
+        flex: TierRates::from_json(val, "flex"),
+        batch: TierRates::from_json(val, "batches"),
+      },
+    );
+  }
+  map
+}
+
+/// Anthropic's and OpenAI's list price for a web search, $10 per 1,000, for
+/// models LiteLLM lists no search price for.
+const DEFAULT_WEB_SEARCH_COST: f64 = 0.01;
+
+/// LiteLLM prices by key, with each key's canonical model id for fuzzy lookups.
+struct PricingTable {
+  prices: HashMap<String, PricingInfo>,
+  /// `(canonical id, key)` sorted by key, so ties resolve the same way on every run.
+  canonical: Vec<(String, String)>,
+}
+
+impl PricingTable {
+  fn new(prices: HashMap<String, PricingInfo>) -> PricingTable {
+    let mut canonical: Vec<(String, String)> =
+      prices.keys().map(|key| (canonical_model(key), key.clone())).collect();
+    canonical.sort_by(|a, b| a.1.cmp(&b.1));
+    PricingTable { prices, canonical }
+  }
+
+  /// The table from the cached LiteLLM prices only, never fetching; empty
+  /// without a cache.
+  fn cached() -> PricingTable {
+    PricingTable::new(read_pricing_cache().map(|json| parse_pricing(&json)).unwrap_or_default())
+  }
+
+  fn len(&self) -> usize {
+    self.prices.len()
+  }
+
+  fn is_empty(&self) -> bool {
+    self.prices.is_empty()
+  }
+
+  /// Looks up pricing for `model`, preferring the provider's own LiteLLM keys
+  /// (e.g. `bedrock/...`) before the vendor's direct prices.
+  fn find(&self, model: &str, provider: Option<Provider>) -> Option<&PricingInfo> {
+    if let Some(provider) = provider {
+      if let Some(p) = find_with(model, &|name| self.try_find_for(name, provider)) {
+        return Some(p);
+      }
+    }
+    find_with(model, &|name| self.try_find(name))
+  }
+
+  /// Cost of `searches` server-side web searches, billed on top of tokens.
+  /// Like their tokens, searches by unpriced models cost nothing.
+  fn web_search_cost(&self, model: &str, provider: Option<Provider>, searches: u64) -> f64 {
+    if searches == 0 {
+      return 0.0;
+    }
+    let Some(p) = self.find(model, provider) else {
+      return 0.0;
+    };
+    let per_search = if p.web_search_cost > 0.0 { p.web_search_cost } else { DEFAULT_WEB_SEARCH_COST };
+    per_search * searches as f64
+  }
+
+  fn try_find(&self, name: &str) -> Option<&PricingInfo> {
+    // exact match
+    if let Some(p) = self.prices.get(name) {
+      return Some(p);
+    }
+    // with provider prefix
+    for prefix in ["anthropic/", "openai/", "azure/", "google/", "vertex_ai/", "gemini/"] {
+      if let Some(p) = self.prices.get(&format!("{prefix}{name}")) {
+        return Some(p);
+      }
+    }
+    self.closest(name, |_| true)
+  }
+
+  /// Only keys under the provider's prefixes, e.g. `vertex_ai/claude-sonnet-4@20250514`.
+  fn try_find_for(&self, name: &str, provider: Provider) -> Option<&PricingInfo> {
+    let prefixes = provider.key_prefixes();
+    let owned = |key: &str| prefixes.iter().any(|prefix| key.starts_with(prefix));
+    if owned(name) {
+      if let Some(p) = self.prices.get(name) {
+        return Some(p);
+      }
+    }
+    for prefix in prefixes {
+      if let Some(p) = self.prices.get(&format!("{prefix}{name}")) {
+        return Some(p);
+      }
+    }
+    self.closest(name, owned)
+  }
+
+  /// Fuzzy match among the keys `filter` accepts, ranked by `match_rank`.
+  /// Equally good matches prefer the key with the least routing around the
+  /// model id (a direct key over `vertex_ai/...`, `anthropic.` over a
+  /// `bedrock/<region>/` key), then the first key in sorted order.
+  fn closest(&self, name: &str, filter: impl Fn(&str) -> bool) -> Option<&PricingInfo> {
+    let wanted = canonical_model(name);
+    if wanted.is_empty() {
+      return None;
+    }
+    self
+      .canonical
+      .iter()
+      .filter(|(_, key)| filter(key))
+      .filter_map(|(id, key)| Some((match_rank(&wanted, id)?, key.len().saturating_sub(id.len()), key)))
+      .min_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)))
+      .and_then(|(_, _, key)| self.prices.get(key))
+  }
+}
+
+/// Reduces a model name or LiteLLM key to a bare, lowercase model id so the
+/// spellings providers use compare equal: `vertex_ai/claude-sonnet-4@20250514`,
+/// `bedrock/us-east-1/anthropic.claude-sonnet-4-20250514-v1:0` and
+/// `us.anthropic.claude-sonnet-4-20250514-v1:0` all become `claude-sonnet-4-20250514`.
+fn canonical_model(name: &str) -> String {
+  let lower = name.to_lowercase();
+  // Routing prefixes such as `vertex_ai/`, `bedrock/` or `bedrock/<region>/`.
+  let mut id = lower.rsplit('/').next().unwrap_or_default();
+  // Bedrock cross-region inference profiles.
+  for region in ["us.", "eu.", "apac.", "global.", "us-gov."] {
+    if let Some(rest) = id.strip_prefix(region) {
+      id = rest;
+      break;
+    }
+  }
+  id = id.strip_prefix("anthropic.").unwrap_or(id);
+  // Bedrock model versions such as `-v1:0`.
+  if let Some((base, version)) = id.rsplit_once("-v") {
+    let is_version = version
+      .split(':')
+      .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()));
+    if is_version {
+      id = base;
+    }
+  }
+  // Vertex dates: `@20250514`.
+  id.replace('@', "-")
+}
+
+/// How well a key's canonical `id` matches the `wanted` one, lower is better:
+/// equal; `wanted` plus a `-` suffix such as a date, shortest first; a shorter
+/// id that `wanted` extends with a `-` suffix, longest first; then either
+/// containing the other, longest first.
+fn match_rank(wanted: &str, id: &str) -> Option<(u8, isize)> {
+  let len = id.len() as isize;
+  let extends = |long: &str, short: &str| long.strip_prefix(short).is_some_and(|rest| rest.starts_with('-'));
+  if id.is_empty() {
+    None
+  } else if id == wanted {
+    Some((0, 0))
+  } else if extends(id, wanted) {
+    Some((1, len))
+  } else if extends(wanted, id) {
+    Some((2, -len))
+  } else if id.contains(wanted) || wanted.contains(id) {
+    Some((3, -len))
+  } else {
+    None
+  }
+}
+
+static PRICING: Lazy<PricingTable> = Lazy::new(|| PricingTable::new(load_pricing()));
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PricingStatus {
+  pub source: String,
+  pub loaded: bool,
+  pub model_count: usize,
+}
+
+fn pricing_status() -> PricingStatus {
+  PricingStatus {
+    source: PRICING_URL.to_string(),
+    loaded: !PRICING.is_empty(),
+    model_count: PRICING.len(),
+  }
+}
+
+/// Tries `try_find` on `model` and on its normalized variants.
+fn find_with<'a>(
+  model: &str,
+  try_find: &dyn Fn(&str) -> Option<&'a PricingInfo>,
+) -> Option<&'a PricingInfo> {
+  fn strip_date_suffix(name: &str) -> Option<&str> {
+    let (base, suffix) = name.rsplit_once('-')?;
+    if suffix.len() == 8 && suffix.chars().all(|c| c.is_ascii_digit()) {
+      Some(base)
+    } else {
+      None
+    }
+  }
+
+  // Try original name first.
+  if let Some(p) = try_find(model) {
+    return Some(p);
+  }
+
+  // Normalize "-thinking" suffix and retry.
+  if let Some(base_model) = model.strip_suffix("-thinking") {
+    if let Some(p) = try_find(base_model) {
+      return Some(p);
+    }
+    // If still not found, also try stripping a trailing date version (e.g. "-20250918").
+    if let Some(no_date) = strip_date_suffix(base_model) {
+      if let Some(p) = try_find(no_date) {
+        return Some(p);
+      }
+    }
+  }
+
+  // Handle variants where "-thinking" appears before the date: "...-thinking-20250918".
+  if let Some(no_date) = strip_date_suffix(model) {
+    if let Some(p) = try_find(no_date) {
+      return Some(p);
+    }
+    if let Some(no_date_no_thinking) = no_date.strip_suffix("-thinking") {
+      if let Some(p) = try_find(no_date_no_thinking) {
+        return Some(p);
+      }
+    }
+  }
+
+  // Strip quality suffixes like "-high", "-low", "-medium" (e.g. gemini-3-pro-high → gemini-3-pro)
+  for suffix in ["-high", "-low", "-medium"] {
+    if let Some(base) = model.strip_suffix(suffix) {
+      if let Some(p) = try_find(base) {
+        return Some(p);
+      }
+    }
+  }
+
+  None
+}
+
+const TIERED_THRESHOLD: u64 = 200_000;
+
+fn tiered_cost(tokens: u64, base_price: f64, above_price: f64) -> f64 {
+  if tokens == 0 {
+    return 0.0;
+  }
+  if above_price > 0.0 && tokens > TIERED_THRESHOLD {
+    let below = TIERED_THRESHOLD as f64 * base_price;
+    let above = (tokens - TIERED_THRESHOLD) as f64 * above_price;
+    below + above
+  } else {
+    tokens as f64 * base_price
+  }
+}
+
+fn is_zero(n: &u64) -> bool {
+  *n == 0
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageEntry {
+  pub timestamp: String,
+  pub tool: String,
+  pub model: String,
+  pub input_tokens: u64,
+  pub output_tokens: u64,
+  pub cache_read_tokens: u64,
+  pub cache_write_tokens: u64,
+  pub total_tokens: u64,
+  pub cost: f64,
+  /// Cost in USD as recorded by the tool itself, if it logs one.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub logged_cost: Option<f64>,
+  /// Cloud platform serving the model, from the provider rules.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub provider: Option<Provider>,
+  /// Priority, flex or batch processing, when the log records it.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub service_tier: Option<ServiceTier>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub session_id: Option<String>,
+  /// Made by a subagent (e.g. Claude's Task tool) rather than the main conversation.
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub sidechain: bool,
+  /// The subagent that made the request, when the log names it.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub agent_id: Option<String>,
+  /// Tools the model called in this response, one name per call.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub tool_calls: Vec<String>,
+  /// Web searches run server-side for this request, billed per search and
+  /// included in `cost`.
+  #[serde(default, skip_serializing_if = "is_zero")]
+  pub web_search_requests: u64,
+  /// Working directory the agent ran in, when the log records it.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub project: Option<String>,
+  /// Set on entries imported from another machine's export bundle.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub machine_id: Option<String>,
+}
+
+impl UsageEntry {
+  /// The entry's timestamp in UTC, if it is valid RFC 3339.
+  pub fn utc_time(&self) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&self.timestamp)
+      .ok()
+      .map(|dt| dt.with_timezone(&Utc))
+  }
+
+  /// The entry's timestamp in local time, if it is valid RFC 3339.
+  pub fn local_time(&self) -> Option<DateTime<Local>> {
+    self.utc_time().map(|dt| dt.with_timezone(&Local))
+  }
+}
+
+struct ScanState {
+  file_states: HashMap<String, FileState>,
+  cached_entries: Vec<UsageEntry>,
+  /// Mode the costs in `cached_entries` were computed with.
+  cost_mode: CostMode,
+}
+
+const SCAN_INDEX_FILE: &str = "scan_index.json";
+const SCAN_INDEX_VERSION: u32 = 8;
+
+/// On-disk copy of `ScanState`, shared by the app and the CLI so either can
+/// resume incrementally instead of rescanning every log.
+#[derive(Serialize)]
+struct ScanIndexRef<'a> {
+  version: u32,
+  file_states: &'a HashMap<String, FileState>,
+  entries: &'a [UsageEntry],
+  cost_mode: CostMode,
+}
+
+#[derive(Deserialize)]
+struct ScanIndex {
+  version: u32,
+  file_states: HashMap<String, FileState>,
+  entries: Vec<UsageEntry>,
+  #[serde(default)]
+  cost_mode: CostMode,
+}
+
+static SCAN_STATE: Lazy<Mutex<ScanState>> = Lazy::new(|| Mutex::new(ScanState::load_index()));
+
+impl ScanState {
+  fn load_index() -> ScanState {
+    let cost_mode = cost_mode::load_mode();
+    // Without an index every log is rescanned; imported entries only live in the import store.
+    let empty = || ScanState {
+      file_states: HashMap::new(),
+      cached_entries: bundle::imported_entries(cost_mode),
+      cost_mode,
+    };
+    let Some(path) = app_cache_dir().map(|d| d.join(SCAN_INDEX_FILE)) else {
+      return empty();
+    };
+    let Ok(raw) = fs::read(&path) else {
+      return empty();
+    };
+    match serde_json::from_slice::<ScanIndex>(&raw) {
+      Ok(index) if index.version == SCAN_INDEX_VERSION => {
+        let mut state = ScanState {
+          file_states: index.file_states,
+          cached_entries: index.entries,
+          cost_mode: index.cost_mode,
+        };
+        state.set_cost_mode(cost_mode);
+        state
+      }
+      Ok(_) => empty(),
+      Err(e) => {
+        log::warn!("Ignoring unreadable scan index {}: {e}", path.display());
+        empty()
+      }
+    }
+  }
+
+  fn save_index(&self) {
+    let Some(path) = app_cache_dir().map(|d| d.join(SCAN_INDEX_FILE)) else {
+      return;
+    };
+    let index = ScanIndexRef {
+      version: SCAN_INDEX_VERSION,
+      file_states: &self.file_states,
+      entries: &self.cached_entries,
+      cost_mode: self.cost_mode,
+    };
+    let result = serde_json::to_vec(&index)
+      .map_err(|e| e.to_string())
+      .and_then(|json| write_atomic(&path, &json));
+    if let Err(e) = result {
+      log::warn!("Failed to save scan index: {e}");
+    }
+    statusline::save_summary(self);
+  }
+
+  /// Replaces imported entries with the current contents of the import store.
+  fn reload_imports(&mut self) {
+    self.cached_entries.retain(|e| e.machine_id.is_none());
+    self.cached_entries.extend(bundle::imported_entries(self.cost_mode));
+  }
+
+  /// Recomputes cached costs for `mode` from the tokens and logged costs
+  /// already stored, without rescanning. Returns whether anything changed.
+  fn set_cost_mode(&mut self, mode: CostMode) -> bool {
+    if mode == self.cost_mode {
+      return false;
+    }
+    mode.apply(&mut self.cached_entries, &PRICING);
+    self.cost_mode = mode;
+    true
+  }
+
+  /// Appends entries from log content added since the last scan. On a fresh
+  /// state this amounts to a full scan.
+  fn scan_incremental(&mut self) -> usize {
+    let before = self.cached_entries.len();
+    let added = self.scan_sources(&registry(), &providers::load_rules(), &PRICING);
+    let len = self.cached_entries.len();
+    if len == before + added {
+      otlp::record(&self.cached_entries[before..]);
+    } else {
+      // Entries a source replaced were already recorded.
+      otlp::record_full(&self.cached_entries);
+    }
+    added
+  }
+
+  /// Appends new entries from `sources`, tagged per `rules` and priced once
+  /// with `pricing`, and drops local entries a source replaced. Returns how
+  /// many were added.
+  fn scan_sources(
+    &mut self,
+    sources: &[Box<dyn UsageSource>],
+    rules: &[ProviderRule],
+    pricing: &PricingTable,
+  ) -> usize {
+    let ScanState { file_states, cached_entries, cost_mode } = self;
+    let mut added = Vec::new();
+    for source in sources {
+      let scan = scan_source(source.as_ref(), file_states, rules);
+      if scan.replaces_earlier {
+        let tool = source.tool();
+        cached_entries.retain(|e| e.machine_id.is_some() || e.tool != tool);
+      }
+      added.extend(scan.entries);
+    }
+    cost_mode.apply(&mut added, pricing);
+    let count = added.len();
+    cached_entries.extend(added);
+    count
+  }
+}
+
+/// Reads every file of `sources` from the start and prices the entries with
+/// `pricing` per `cost_mode`. The file positions reached are returned with the
+/// entries and must be kept: the next incremental scan resumes from them
+/// instead of counting the same lines a second time.
+fn scan_from_start(
+  sources: &[Box<dyn UsageSource>],
+  rules: &[ProviderRule],
+  cost_mode: CostMode,
+  pricing: &PricingTable,
+) -> (Vec<UsageEntry>, HashMap<String, FileState>) {
+  let mut file_states = HashMap::new();
+  let mut out = Vec::new();
+  for source in sources {
+    out.extend(scan_source(source.as_ref(), &mut file_states, rules).entries);
+  }
+  cost_mode.apply(&mut out, pricing);
+  (out, file_states)
+}
+
+/// Matches the directory Tauri uses for `app_config_dir` with our bundle identifier.
+fn app_config_dir() -> Option<PathBuf> {
+  Some(dirs::config_dir()?.join("com.token-viewer.app"))
+}
+
+/// Matches the directory Tauri uses for `app_cache_dir` with our bundle identifier.
+fn app_cache_dir() -> Option<PathBuf> {
+  Some(dirs::cache_dir()?.join("com.token-viewer.app"))
+}
+
+/// Writes via a temporary file and rename so concurrent readers (app and CLI)
+/// never see a partial file.
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+  }
+  let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+  fs::write(&tmp, bytes).map_err(|e| e.to_string())?;
+  fs::rename(&tmp, path).map_err(|e| {
+    let _ = fs::remove_file(&tmp);
+    e.to_string()
+  })
+}
+
+/// Reads a JSON settings file from the config directory. Missing files yield
+/// `None`; malformed ones are logged and also yield `None`.
+fn read_config<T: DeserializeOwned>(file_name: &str) -> Option<T> {
+  let path = app_config_dir()?.join(file_name);
+  let raw = fs::read_to_string(&path).ok()?;
+  match serde_json::from_str(&raw) {
+    Ok(v) => Some(v),
+    Err(e) => {
+      log::warn!("Failed to parse {}: {e}", path.display());
+      None
+    }
+  }
+}
+
+fn write_config<T: Serialize + ?Sized>(file_name: &str, value: &T) -> Result<(), String> {
+  let dir = app_config_dir().ok_or("no config directory available")?;
+  fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+  let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+  fs::write(dir.join(file_name), json).map_err(|e| e.to_string())
+}
+
+fn host_name() -> String {
+  std::env::var("HOSTNAME")
+    .or_else(|_| std::env::var("COMPUTERNAME"))
+    .ok()
+    .or_else(|| fs::read_to_string("/etc/hostname").ok())
+    .map(|h| h.trim().to_string())
+    .filter(|h| !h.is_empty())
+    .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn user_name() -> String {
+  std::env::var("USER")
+    .or_else(|_| std::env::var("USERNAME"))
+    .ok()
+    .filter(|u| !u.trim().is_empty())
+    .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn home_glob_prefix() -> Option<String> {
+  let home = dirs::home_dir()?;
+  Some(home.to_string_lossy().replace('\\', "/"))
+}
+
+fn glob_paths(patterns: &[String]) -> Vec<PathBuf> {
+  let mut out = Vec::new();
+  let mut seen = HashSet::<String>::new();
+
+  for pattern in patterns {
+    let entries = match glob(pattern) {
+      Ok(it) => it,
+      Err(err) => {
+        log::debug!("Invalid glob pattern '{pattern}': {err}");
+        continue;
+      }
+    };
+
+    for entry in entries {
+      match entry {
+        Ok(path) => {
+          let key = path.to_string_lossy().to_string();
+          if seen.insert(key) {
+            out.push(path);
+          }
+        }
+        Err(err) => {
+          log::debug!("Glob error for pattern '{pattern}': {err}");
+        }
+      }
+    }
+  }
+
+  out
+}
+
+fn file_mtime_rfc3339(path: &Path) -> Option<String> {
+  let st = fs::metadata(path).ok()?.modified().ok()?;
+  let dt: DateTime<Utc> = st.into();
+  Some(dt.to_rfc3339())
+}
+
+fn normalize_epoch(epoch: i64) -> Option<String> {
+  let dt = if epoch.unsigned_abs() >= 1_000_000_000_000 {
+    Utc.timestamp_millis_opt(epoch).single()?
+  } else {
+    Utc.timestamp_opt(epoch, 0).single()?
+  };
+  Some(dt.to_rfc3339())
+}
+
+fn normalize_timestamp(value: Option<&Value>) -> Option<String> {
+  match value? {
+    Value::String(s) => {
+      let s = s.trim();
+      if s.is_empty() {
+        return None;
+      }
+      if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(dt.with_timezone(&Utc).to_rfc3339());
+      }
+      if s.chars().all(|c| c.is_ascii_digit()) {
+        if let Ok(epoch) = s.parse::<i64>() {
+          return normalize_epoch(epoch);
+        }
+      }
+      Some(s.to_string())
+    }
+    Value::Number(n) => {
+      if let Some(i) = n.as_i64() {
+        normalize_epoch(i)
+      } else if let Some(u) = n.as_u64() {
+        if u <= i64::MAX as u64 {
+          normalize_epoch(u as i64)
+        } else {
+          None
+        }
+      } else {
+        None
+      }
+    }
+    _ => None,
+  }
+}
+
+fn value_u64(value: Option<&Value>) -> u64 {
+  match value {
+    Some(Value::Number(n)) => n.as_u64().unwrap_or_else(|| n.as_i64().unwrap_or(0).max(0) as u64),
+    Some(Value::String(s)) => s.trim().parse::<u64>().unwrap_or(0),
+    _ => 0,
+  }
+}
+
+fn value_f64(value: Option<&Value>) -> f64 {
+  match value {
+    Some(Value::Number(n)) => n.as_f64().unwrap_or(0.0),
+    Some(Value::String(s)) => s.trim().parse::<f64>().unwrap_or(0.0),
+    _ => 0.0,
+  }
+}
+
+fn total_tokens_with_cache(input: u64, output: u64, cache_read: u64, cache_write: u64) -> u64 {
+  input
+    .saturating_add(output)
+    .saturating_add(cache_read)
+    .saturating_add(cache_write)
+}
+
+fn total_tokens_without_cache(input: u64, output: u64) -> u64 {
+  input.saturating_add(output)
+}
+
+fn scan_tool_usage_impl(tool: &str) -> Vec<UsageEntry> {
+  match find_source(tool) {
+    Some(source) => {
+      let mut entries = scan_source(source.as_ref(), &mut HashMap::new(), &providers::load_rules()).entries;
+      cost_mode::load_mode().apply(&mut entries, &PRICING);
+      entries
+    }
+    None => Vec::new(),
+  }
+}
+
+#[tauri::command]
+async fn scan_claude_usage() -> Vec<UsageEntry> {
+  tauri::async_runtime::spawn_blocking(|| scan_tool_usage_impl("Claude"))
+    .await
+    .unwrap_or_default()
+}
+
+#[tauri::command]
+async fn scan_codex_usage() -> Vec<UsageEntry> {
+  tauri::async_runtime::spawn_blocking(|| scan_tool_usage_impl("Codex"))
+    .await
+    .unwrap_or_default()
+}
+
+#[tauri::command]
+async fn scan_opencode_usage() -> Vec<UsageEntry> {
+  tauri::async_runtime::spawn_blocking(|| scan_tool_usage_impl("OpenCode"))
+    .await
+    .unwrap_or_default()
+}
+
+#[tauri::command]
+async fn scan_gemini_usage() -> Vec<UsageEntry> {
+  tauri::async_runtime::spawn_blocking(|| scan_tool_usage_impl("Gemini"))
+    .await
+    .unwrap_or_default()
+}
+
+#[tauri::command]
+async fn scan_source_usage(tool: String) -> Vec<UsageEntry> {
+  tauri::async_runtime::spawn_blocking(move || scan_tool_usage_impl(&tool))
+    .await
+    .unwrap_or_default()
+}
+
+#[tauri::command]
+fn list_sources() -> Vec<String> {
+  registry().iter().map(|s| s.tool().to_string()).collect()
+}
+
+#[tauri::command]
+fn get_custom_sources() -> Vec<CustomSourceDef> {
+  custom_sources::load_custom_sources()
+}
+
+#[tauri::command]
+fn set_custom_sources(sources: Vec<CustomSourceDef>) -> Result<(), String> {
+  custom_sources::save_custom_sources(&sources)
+}
+
+#[tauri::command]
+fn get_budgets() -> Vec<Budget> {
+  budgets::load_budgets()
+}
+
+#[tauri::command]
+fn set_budgets(budgets: Vec<Budget>) -> Result<(), String> {
+  budgets::save_budgets(&budgets)
+}
+
+#[tauri::command]
+fn get_budget_status() -> Vec<BudgetStatus> {
+  let budgets = budgets::load_budgets();
+  match SCAN_STATE.lock() {
+    Ok(state) => budgets::evaluate(&budgets, &state.cached_entries, Local::now()),
+    Err(_) => Vec::new(),
+  }
+}
+
+#[tauri::command]
+fn get_anomalies(since: Option<NaiveDate>) -> Vec<Anomaly> {
+  match SCAN_STATE.lock() {
+    Ok(state) => anomalies::find(&state.cached_entries, since),
+    Err(_) => Vec::new(),
+  }
+}
+
+#[tauri::command]
+fn get_anomaly_settings() -> AnomalySettings {
+  anomalies::load_settings()
+}
+
+#[tauri::command]
+fn set_anomaly_settings(settings: AnomalySettings) -> Result<(), String> {
+  anomalies::save_settings(&settings)
+}
+
+#[tauri::command]
+fn get_forecast() -> Option<Forecast> {
+  let budgets = budgets::load_budgets();
+  let state = SCAN_STATE.lock().ok()?;
+  Some(forecast::forecast(&state.cached_entries, &budgets, Local::now(), &Converter::load()))
+}
+
+#[tauri::command]
+fn get_plans() -> Vec<Plan> {
+  plans::load_plans()
+}
+
+#[tauri::command]
+fn set_plans(plans: Vec<Plan>) -> Result<(), String> {
+  plans::save_plans(&plans)
+}
+
+#[tauri::command]
+fn get_plan_value(since: Option<NaiveDate>, until: Option<NaiveDate>) -> Vec<PlanPeriodValue> {
+  let plans = plans::load_plans();
+  match SCAN_STATE.lock() {
+    Ok(state) => plans::compare(&plans, &state.cached_entries, since, until, &Converter::load(), &PRICING),
+    Err(_) => Vec::new(),
+  }
+}
+
+/// Aggregates with costs in the display currency, and that currency.
+#[derive(Debug, Clone, Serialize)]
+pub struct AggregateReport {
+  pub currency: DisplayCurrency,
+  pub rows: Vec<AggregateRow>,
+}
+
+#[tauri::command]
+fn get_aggregates(group_by: GroupBy, filter: Option<EntryFilter>) -> AggregateReport {
+  let filter = filter.unwrap_or_default();
+  let converter = Converter::load();
+  let rows = match SCAN_STATE.lock() {
+    Ok(state) => aggregate::aggregate(&converter.entries(&state.cached_entries), &filter, group_by),
+    Err(_) => Vec::new(),
+  };
+  AggregateReport { currency: converter.display(), rows }
+}
+
+#[tauri::command]
+fn get_cache_metrics(group_by: GroupBy, filter: Option<EntryFilter>) -> Vec<CacheMetrics> {
+  let filter = filter.unwrap_or_default();
+  match SCAN_STATE.lock() {
+    Ok(state) => cache::cache_metrics(&state.cached_entries, &filter, group_by, &Converter::load(), &PRICING),
+    Err(_) => Vec::new(),
+  }
+}
+
+#[tauri::command]
+fn list_sessions(
+  filter: Option<EntryFilter>,
+  sort: Option<SessionSort>,
+  ascending: Option<bool>,
+  offset: Option<usize>,
+  limit: Option<usize>,
+) -> Option<SessionPage> {
+  let filter = filter.unwrap_or_default();
+  let state = SCAN_STATE.lock().ok()?;
+  let entries = Converter::load().entries(&state.cached_entries);
+  Some(sessions::list_sessions(
+    &entries,
+    &filter,
+    sort.unwrap_or_default(),
+    ascending.unwrap_or(false),
+    offset.unwrap_or(0),
+    limit.unwrap_or(sessions::DEFAULT_PAGE_SIZE),
+  ))
+}
+
+#[tauri::command]
+fn get_tool_call_breakdown(filter: Option<EntryFilter>) -> ToolCallBreakdown {
+  let filter = filter.unwrap_or_default();
+  match SCAN_STATE.lock() {
+    Ok(state) => tool_calls::breakdown(&state.cached_entries, &filter, &Converter::load(), &PRICING),
+    Err(_) => ToolCallBreakdown::default(),
+  }
+}
+
+#[tauri::command]
+fn get_usage_blocks() -> Vec<UsageBlock> {
+  match SCAN_STATE.lock() {
+    Ok(state) => aggregate::usage_blocks(&Converter::load().entries(&state.cached_entries), Utc::now()),
+    Err(_) => Vec::new(),
+  }
+}
+
+#[tauri::command]
+async fn get_pricing_status() -> PricingStatus {
+  tauri::async_runtime::spawn_blocking(pricing_status)
+    .await
+    .unwrap_or_else(|_| PricingStatus {
+      source: PRICING_URL.to_string(),
+      loaded: false,
+      model_count: 0,
+    })
+}
+
+#[tauri::command]
+fn get_currency_settings() -> CurrencySettings {
+  currency::load_settings()
+}
+
+#[tauri::command]
+fn set_currency_settings(settings: CurrencySettings) -> Result<(), String> {
+  currency::save_settings(&settings)?;
+  if let Ok(state) = SCAN_STATE.lock() {
+    otlp::record_full(&state.cached_entries);
+  }
+  Ok(())
+}
+
+#[tauri::command]
+fn get_provider_rules() -> Vec<ProviderRule> {
+  providers::load_rules()
+}
+
+#[tauri::command]
+fn set_provider_rules(rules: Vec<ProviderRule>) -> Result<(), String> {
+  providers::save_rules(&rules)
+}
+
+#[tauri::command]
+fn get_cost_mode() -> CostMode {
+  cost_mode::load_mode()
+}
+
+#[tauri::command]
+fn set_cost_mode(mode: CostMode) -> Result<(), String> {
+  cost_mode::save_mode(mode)?;
+  let mut state = SCAN_STATE.lock().map_err(|_| "scan state unavailable".to_string())?;
+  if state.set_cost_mode(mode) {
+    state.save_index();
+    otlp::record_full(&state.cached_entries);
+  }
+  Ok(())
+}
+
+#[tauri::command]
+fn get_api_settings() -> ApiSettings {
+  api_server::load_settings()
+}
+
+#[tauri::command]
+fn set_api_settings(settings: ApiSettings) -> Result<ApiSettings, String> {
+  api_server::apply_settings(settings)
+}
+
+#[tauri::command]
+fn get_otlp_settings() -> OtlpSettings {
+  otlp::load_settings()
+}
+
+#[tauri::command]
+fn set_otlp_settings(settings: OtlpSettings) -> Result<(), String> {
+  otlp::apply_settings(settings)
+}
+
+#[tauri::command]
+async fn export_bundle(path: String) -> Result<usize, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    let mut state = SCAN_STATE.lock().map_err(|_| "scan state unavailable".to_string())?;
+    if state.scan_incremental() > 0 {
+      state.save_index();
+    }
+    let bundle = bundle::Bundle::from_local(&state.cached_entries);
+    bundle::write_bundle(Path::new(&path), &bundle)?;
+    Ok(bundle.entries.len())
+  })
+  .await
+  .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn import_bundle(path: String) -> Result<ImportSummary, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    let summary = bundle::import_bundle(Path::new(&path))?;
+    if let Ok(mut state) = SCAN_STATE.lock() {
+      state.reload_imports();
+      state.save_index();
+    }
+    Ok(summary)
+  })
+  .await
+  .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn export_html_report(
+  path: String,
+  since: Option<NaiveDate>,
+  until: Option<NaiveDate>,
+) -> Result<(), String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    let state = SCAN_STATE.lock().map_err(|_| "scan state unavailable".to_string())?;
+    html_report::write(Path::new(&path), &state.cached_entries, since, until, &Converter::load(), &PRICING)
+  })
+  .await
+  .map_err(|e| e.to_string())?
+}
+
+/// Entries for the dashboard, with costs in the display currency. Budgets,
+/// the index and exports keep working on the USD entries.
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageData {
+  pub currency: DisplayCurrency,
+  pub entries: Vec<UsageEntry>,
+}
+
+impl UsageData {
+  fn converted(entries: &[UsageEntry]) -> UsageData {
+    let converter = Converter::load();
+    UsageData {
+      currency: converter.display(),
+      entries: converter.entries(entries).into_owned(),
+    }
+  }
+}
+
+#[tauri::command]
+async fn scan_all_usage(app: tauri::AppHandle) -> UsageData {
+  tauri::async_runtime::spawn_blocking(move || {
+    let cost_mode = cost_mode::load_mode();
+    let (mut out, file_states) = scan_from_start(&registry(), &providers::load_rules(), cost_mode, &PRICING);
+    out.extend(bundle::imported_entries(cost_mode));
+    budgets::check_and_notify(&app, &out);
+    anomalies::check_and_notify(&app, &out);
+    otlp::record_full(&out);
+    let data = UsageData::converted(&out);
+
+    // Store full results and file positions for future incremental scans
+    if let Ok(mut state) = SCAN_STATE.lock() {
+      state.file_states = file_states;
+      state.cached_entries = out;
+      state.cost_mode = cost_mode;
+      state.save_index();
+    }
+    data
+  }).await.unwrap_or_else(|_| UsageData::converted(&[]))
+}
+
+#[tauri::command]
+async fn scan_all_usage_incremental(app: tauri::AppHandle) -> UsageData {
+  tauri::async_runtime::spawn_blocking(move || {
+    let mut state = match SCAN_STATE.lock() {
+      Ok(s) => s,
+      Err(_) => return UsageData::converted(&[]),
+    };
+
+    if state.scan_incremental() > 0 {
+      state.save_index();
+    }
+    budgets::check_and_notify(&app, &state.cached_entries);
+    anomalies::check_and_notify(&app, &state.cached_entries);
+    UsageData::converted(&state.cached_entries)
+  }).await.unwrap_or_else(|_| UsageData::converted(&[]))
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
   tauri::Builder::default()
+    .plugin(tauri_plugin_notification::init())
     .setup(|app| {
       if cfg!(debug_assertions) {
         app.handle().plugin(
@@ -9,8 +1330,265 @@ pub fn run() {
             .build(),
         )?;
       }
+      api_server::start_from_settings();
+      otlp::start_exporter();
       Ok(())
     })
+    .invoke_handler(tauri::generate_handler![
+      scan_claude_usage,
+      scan_codex_usage,
+      scan_opencode_usage,
+      scan_gemini_usage,
+      scan_source_usage,
+      list_sources,
+      get_custom_sources,
+      set_custom_sources,
+      get_budgets,
+      set_budgets,
+      get_budget_status,
+      get_forecast,
+      get_anomalies,
+      get_anomaly_settings,
+      set_anomaly_settings,
+      get_plans,
+      set_plans,
+      get_plan_value,
+      get_aggregates,
+      get_cache_metrics,
+      list_sessions,
+      get_tool_call_breakdown,
+      get_usage_blocks,
+      get_pricing_status,
+      get_currency_settings,
+      set_currency_settings,
+      get_provider_rules,
+      set_provider_rules,
+      get_cost_mode,
+      set_cost_mode,
+      get_api_settings,
+      set_api_settings,
+      get_otlp_settings,
+      set_otlp_settings,
+      export_bundle,
+      import_bundle,
+      export_html_report,
+      scan_all_usage,
+      scan_all_usage_incremental
+    ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use sources::{ClaudeSource, OpenCodeSource};
+  use std::io::Write;
+
+  /// Claude logs under a fixed directory instead of the home directory.
+  struct ClaudeAt(String);
+
+  impl UsageSource for ClaudeAt {
+    fn tool(&self) -> &str {
+      ClaudeSource.tool()
+    }
+
+    fn roots(&self, _home: &str) -> Vec<String> {
+      vec![self.0.clone()]
+    }
+
+    fn file_patterns(&self) -> Vec<String> {
+      vec!["*.jsonl".to_string()]
+    }
+
+    fn scan_file(&self, path: &Path, state: &mut FileState) -> Vec<UsageEntry> {
+      ClaudeSource.scan_file(path, state)
+    }
+  }
+
+  #[test]
+  fn incremental_scan_after_full_scan_reads_only_new_lines() {
+    let dir = std::env::temp_dir().join(format!("token-viewer-{}-full-scan", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("session.jsonl");
+    let line = r#"{"timestamp":"2025-01-01T00:00:00Z","message":{"model":"claude-sonnet-4","usage":{"input_tokens":10,"output_tokens":5}}}"#;
+    let append = |n: usize| {
+      let mut f = fs::OpenOptions::new().create(true).append(true).open(&log).unwrap();
+      for _ in 0..n {
+        writeln!(f, "{line}").unwrap();
+      }
+    };
+    append(2);
+
+    let sources: Vec<Box<dyn UsageSource>> = vec![Box::new(ClaudeAt(dir.to_string_lossy().replace('\\', "/")))];
+    let rates = PricingInfo { input_cost_per_token: 1e-3, output_cost_per_token: 2e-3, ..Default::default() };
+    let pricing = PricingTable::new(HashMap::from([("claude-sonnet-4".to_string(), rates)]));
+    let (entries, file_states) = scan_from_start(&sources, &[], CostMode::Auto, &pricing);
+    assert_eq!(entries.len(), 2);
+    let mut state = ScanState { file_states, cached_entries: entries, cost_mode: CostMode::Auto };
+
+    assert_eq!(state.scan_sources(&sources, &[], &pricing), 0);
+    append(1);
+    assert_eq!(state.scan_sources(&sources, &[], &pricing), 1);
+    assert_eq!(state.cached_entries.len(), 3);
+    // Priced once: 10 input and 5 output tokens each.
+    assert!(state.cached_entries.iter().all(|e| (e.cost - 0.02).abs() < 1e-12));
+
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  /// OpenCode data under a fixed directory instead of the home directory.
+  struct OpenCodeAt(String);
+
+  impl UsageSource for OpenCodeAt {
+    fn tool(&self) -> &str {
+      OpenCodeSource.tool()
+    }
+
+    fn roots(&self, _home: &str) -> Vec<String> {
+      vec![self.0.clone()]
+    }
+
+    fn file_patterns(&self) -> Vec<String> {
+      OpenCodeSource.file_patterns()
+    }
+
+    fn scan_file(&self, path: &Path, state: &mut FileState) -> Vec<UsageEntry> {
+      OpenCodeSource.scan_file(path, state)
+    }
+
+    fn replaces_other_files(&self, path: &Path) -> bool {
+      OpenCodeSource.replaces_other_files(path)
+    }
+  }
+
+  #[test]
+  fn opencode_database_replaces_messages_counted_from_json_files() {
+    let dir = std::env::temp_dir().join(format!("token-viewer-{}-opencode", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let messages = dir.join("storage/message/ses_a");
+    fs::create_dir_all(&messages).unwrap();
+    let message = r#"{"id":"msg_1","sessionID":"ses_a","role":"assistant","modelID":"claude-sonnet-4","time":{"created":1735689600000,"completed":1735689601000},"tokens":{"input":10,"output":5,"cache":{"read":0,"write":0}}}"#;
+    fs::write(messages.join("msg_1.json"), message).unwrap();
+
+    let sources: Vec<Box<dyn UsageSource>> = vec![Box::new(OpenCodeAt(dir.to_string_lossy().replace('\\', "/")))];
+    let pricing = PricingTable::new(HashMap::new());
+    let mut state = ScanState { file_states: HashMap::new(), cached_entries: Vec::new(), cost_mode: CostMode::Auto };
+    assert_eq!(state.scan_sources(&sources, &[], &pricing), 1);
+
+    // An upgrade migrates the same message into the database.
+    let conn = rusqlite::Connection::open(dir.join("opencode.db")).unwrap();
+    conn
+      .execute_batch("CREATE TABLE message (id TEXT PRIMARY KEY, session_id TEXT NOT NULL, time_created INTEGER, time_updated INTEGER, data TEXT NOT NULL);")
+      .unwrap();
+    conn
+      .execute(
+        "INSERT INTO message (id, session_id, time_created, time_updated, data) VALUES ('msg_1', 'ses_a', 1735689600000, 1735689601000, ?1)",
+        [message],
+      )
+      .unwrap();
+    drop(conn);
+
+    assert_eq!(state.scan_sources(&sources, &[], &pricing), 1);
+    assert_eq!(state.cached_entries.len(), 1);
+    assert_eq!(state.scan_sources(&sources, &[], &pricing), 0);
+    assert_eq!(state.cached_entries.len(), 1);
+
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  /// A table where each key's input price identifies the key.
+  fn pricing_table(keys: &[&str]) -> PricingTable {
+    let prices = keys
+      .iter()
+      .enumerate()
+      .map(|(i, key)| {
+        let p = PricingInfo { input_cost_per_token: (i + 1) as f64, ..Default::default() };
+        (key.to_string(), p)
+      })
+      .collect();
+    PricingTable::new(prices)
+  }
+
+  #[test]
+  fn resolves_vertex_and_bedrock_keys_deterministically() {
+    let keys = [
+      "claude-sonnet-4-20250514",
+      "anthropic/claude-sonnet-4-20250514",
+      "vertex_ai/claude-sonnet-4",
+      "vertex_ai/claude-sonnet-4@20250514",
+      "anthropic.claude-sonnet-4-20250514-v1:0",
+      "us.anthropic.claude-sonnet-4-20250514-v1:0",
+      "bedrock/us-east-1/anthropic.claude-sonnet-4-20250514-v1:0",
+      "bedrock/eu-west-1/anthropic.claude-sonnet-4-20250514-v1:0",
+      "claude-sonnet-4-5-20250929",
+    ];
+    let vertex = Some(Provider::VertexAi);
+    let bedrock = Some(Provider::Bedrock);
+    let cases = [
+      ("claude-sonnet-4@20250514", vertex, Some("vertex_ai/claude-sonnet-4@20250514")),
+      ("claude-sonnet-4-20250514", vertex, Some("vertex_ai/claude-sonnet-4@20250514")),
+      ("claude-sonnet-4", vertex, Some("vertex_ai/claude-sonnet-4")),
+      ("claude-sonnet-4-20250514", bedrock, Some("anthropic.claude-sonnet-4-20250514-v1:0")),
+      ("claude-sonnet-4-20250514-thinking", bedrock, Some("anthropic.claude-sonnet-4-20250514-v1:0")),
+      ("us.anthropic.claude-sonnet-4-20250514-v1:0", bedrock, Some("us.anthropic.claude-sonnet-4-20250514-v1:0")),
+      ("eu.anthropic.claude-sonnet-4-20250514-v1:0", None, Some("claude-sonnet-4-20250514")),
+      ("claude-sonnet-4-5", None, Some("claude-sonnet-4-5-20250929")),
+      ("gpt-5", None, None),
+    ];
+
+    // Fresh tables get fresh hash seeds, so a lookup depending on map order would flip.
+    for _ in 0..20 {
+      let table = pricing_table(&keys);
+      for (model, provider, expected) in cases {
+        let found = table.find(model, provider).map(|p| keys[p.input_cost_per_token as usize - 1]);
+        assert_eq!(found, expected, "{model} via {provider:?}");
+      }
+    }
+  }
+
+  #[test]
+  fn canonical_model_strips_provider_spellings() {
+    let cases = [
+      ("claude-sonnet-4-20250514", "claude-sonnet-4-20250514"),
+      ("vertex_ai/claude-sonnet-4@20250514", "claude-sonnet-4-20250514"),
+      ("Claude-Sonnet-4@20250514", "claude-sonnet-4-20250514"),
+      ("anthropic.claude-3-5-haiku-20241022-v1:0", "claude-3-5-haiku-20241022"),
+      ("bedrock/us-east-1/anthropic.claude-sonnet-4-20250514-v1:0", "claude-sonnet-4-20250514"),
+      ("us.anthropic.claude-sonnet-4-20250514-v1:0", "claude-sonnet-4-20250514"),
+      ("apac.anthropic.claude-sonnet-4-20250514-v2", "claude-sonnet-4-20250514"),
+      // Only numeric `-v` suffixes are versions.
+      ("gpt-4-vision", "gpt-4-vision"),
+      ("mistral-large-v1:beta", "mistral-large-v1:beta"),
+    ];
+    for (name, expected) in cases {
+      assert_eq!(canonical_model(name), expected, "{name}");
+    }
+  }
+
+  #[test]
+  fn batch_requests_over_200k_keep_long_context_rates() {
+    let standard = PricingInfo {
+      input_cost_per_token: 3e-6,
+      output_cost_per_token: 15e-6,
+      input_cost_above_200k: 6e-6,
+      output_cost_above_200k: 22.5e-6,
+      batch: Some(TierRates { input: 1.5e-6, output: 7.5e-6, ..Default::default() }),
+      ..Default::default()
+    };
+    let cost = |p: PricingInfo| p.for_tier(Some(ServiceTier::Batch)).cost(300_000, 1_000, 0, 0);
+    // Half price throughout: 200k at 1.5e-6, 100k at 3e-6 and 1k output at 7.5e-6.
+    let expected = 0.3 + 0.3 + 0.0075;
+    assert!((cost(standard) - expected).abs() < 1e-9);
+
+    // A long-context batch rate listed by LiteLLM wins over the scaled one.
+    let mut listed = standard;
+    listed.batch = Some(TierRates {
+      input: 1.5e-6,
+      output: 7.5e-6,
+      input_above_200k: 2.5e-6,
+      ..Default::default()
+    });
+    assert!((cost(listed) - (0.3 + 0.25 + 0.0075)).abs() < 1e-9);
+  }
+}
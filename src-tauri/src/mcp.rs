@@ -0,0 +1,244 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+
+use crate::aggregate::{self, EntryFilter, GroupBy, Totals};
+use crate::currency::Converter;
+use crate::{UsageEntry, SCAN_STATE};
+
+/// Protocol revision answered when the client asks for one we don't know.
+const PROTOCOL_VERSION: &str = "2025-06-18";
+const SUPPORTED_VERSIONS: [&str; 3] = ["2024-11-05", "2025-03-26", "2025-06-18"];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+fn filter_properties() -> Value {
+  json!({
+    "since": { "type": "string", "description": "First local date to include, YYYY-MM-DD." },
+    "until": { "type": "string", "description": "Last local date to include, YYYY-MM-DD." },
+    "tool": { "type": "string", "description": "Agent name, e.g. Claude, Codex, OpenCode, Gemini." },
+    "model": { "type": "string", "description": "Case-insensitive substring of the model name." },
+    "project": {
+      "type": "string",
+      "description": "Case-insensitive substring of the project working directory, e.g. a repository name or path."
+    }
+  })
+}
+
+fn tool_definitions() -> Value {
+  let mut aggregate_props = filter_properties();
+  aggregate_props["group_by"] = json!({
+    "type": "string",
+    "enum": ["day", "week", "month", "year", "tool", "model", "project", "provider", "thread"],
+    "description": "How to group the totals. Dates use local time; weeks are keyed by their Monday; thread splits main conversation from subagent requests."
+  });
+  json!([
+    {
+      "name": "get_usage_summary",
+      "description": "Total tokens, requests and cost (in the returned currency) of local AI coding agent usage matching the filters.",
+      "inputSchema": { "type": "object", "properties": filter_properties() }
+    },
+    {
+      "name": "get_usage_aggregates",
+      "description": "Tokens, requests and cost (in the returned currency) of local AI coding agent usage grouped by date, tool, model, project, cloud provider or main thread versus subagents.",
+      "inputSchema": { "type": "object", "properties": aggregate_props, "required": ["group_by"] }
+    }
+  ])
+}
+
+#[derive(Deserialize)]
+struct AggregateArgs {
+  group_by: String,
+  #[serde(flatten)]
+  filter: EntryFilter,
+}
+
+fn invalid_arguments(e: serde_json::Error) -> String {
+  format!("invalid arguments (dates must be YYYY-MM-DD): {e}")
+}
+
+/// Runs a tool against freshly scanned entries. `Err` is reported to the
+/// model as a tool error rather than a protocol error.
+fn call_tool(name: &str, args: Value) -> Result<Value, String> {
+  let mut state = SCAN_STATE.lock().map_err(|_| "scan state unavailable".to_string())?;
+  if state.scan_incremental() > 0 {
+    state.save_index();
+  }
+  let converter = Converter::load();
+  run_tool(name, args, &converter.entries(&state.cached_entries), converter.currency())
+}
+
+/// Runs a tool over `entries`, whose costs are already in `currency`.
+fn run_tool(name: &str, args: Value, entries: &[UsageEntry], currency: &str) -> Result<Value, String> {
+  match name {
+    "get_usage_summary" => {
+      let filter: EntryFilter = serde_json::from_value(args).map_err(invalid_arguments)?;
+      let totals = Totals::from_entries(entries.iter().filter(|e| filter.matches(e)));
+      let mut value = serde_json::to_value(totals).map_err(|e| e.to_string())?;
+      value["currency"] = json!(currency);
+      Ok(value)
+    }
+    "get_usage_aggregates" => {
+      let args: AggregateArgs = serde_json::from_value(args).map_err(invalid_arguments)?;
+      let group_by = GroupBy::parse(&args.group_by)
+        .ok_or_else(|| format!("unknown group_by '{}'", args.group_by))?;
+      let rows = aggregate::aggregate(entries, &args.filter, group_by);
+      Ok(json!({ "currency": currency, "rows": rows }))
+    }
+    _ => Err(format!("unknown tool '{name}'")),
+  }
+}
+
+fn tool_result(result: Result<Value, String>) -> Value {
+  match result {
+    Ok(value) => json!({
+      "content": [{ "type": "text", "text": value.to_string() }],
+      "structuredContent": value,
+    }),
+    Err(message) => json!({
+      "content": [{ "type": "text", "text": message }],
+      "isError": true,
+    }),
+  }
+}
+
+/// Handles one JSON-RPC message; returns the response, or `None` for notifications.
+fn handle_message(message: &Value) -> Option<Value> {
+  let id = message.get("id").cloned();
+  let method = message.get("method").and_then(Value::as_str);
+  let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+  let result = match method {
+    // Notifications (no id) such as `notifications/initialized` need no answer.
+    _ if id.is_none() => return None,
+    None => Err((INVALID_REQUEST, "missing method".to_string())),
+    Some("initialize") => {
+      let requested = params.get("protocolVersion").and_then(Value::as_str);
+      let version = requested
+        .filter(|v| SUPPORTED_VERSIONS.contains(v))
+        .unwrap_or(PROTOCOL_VERSION);
+      Ok(json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": {} },
+        "serverInfo": { "name": "token-viewer", "version": env!("CARGO_PKG_VERSION") },
+      }))
+    }
+    Some("ping") => Ok(json!({})),
+    Some("tools/list") => Ok(json!({ "tools": tool_definitions() })),
+    Some("tools/call") => match params.get("name").and_then(Value::as_str) {
+      Some(name) => {
+        let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+        Ok(tool_result(call_tool(name, args)))
+      }
+      None => Err((INVALID_PARAMS, "missing tool name".to_string())),
+    },
+    Some(other) => Err((METHOD_NOT_FOUND, format!("method '{other}' not found"))),
+  };
+
+  Some(match result {
+    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+    Err((code, message)) => {
+      json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+    }
+  })
+}
+
+/// Serves the Model Context Protocol over stdio: one JSON-RPC message per line
+/// on stdin, one response per line on stdout. Returns when stdin closes.
+pub fn serve() -> std::io::Result<()> {
+  let stdin = std::io::stdin();
+  let mut stdout = std::io::stdout();
+  for line in stdin.lock().lines() {
+    let line = line?;
+    if line.trim().is_empty() {
+      continue;
+    }
+    let response = match serde_json::from_str::<Value>(&line) {
+      Ok(message) => handle_message(&message),
+      Err(e) => Some(json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": { "code": PARSE_ERROR, "message": e.to_string() },
+      })),
+    };
+    if let Some(response) = response {
+      writeln!(stdout, "{response}")?;
+      stdout.flush()?;
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn request(method: &str, params: Value) -> Value {
+    handle_message(&json!({ "jsonrpc": "2.0", "id": 7, "method": method, "params": params })).unwrap()
+  }
+
+  #[test]
+  fn answers_initialize_with_a_supported_version() {
+    let response = request("initialize", json!({ "protocolVersion": "2025-03-26" }));
+    assert_eq!(response["id"], 7);
+    assert_eq!(response["result"]["protocolVersion"], "2025-03-26");
+    assert_eq!(response["result"]["capabilities"], json!({ "tools": {} }));
+    assert_eq!(response["result"]["serverInfo"]["name"], "token-viewer");
+
+    let response = request("initialize", json!({ "protocolVersion": "1999-01-01" }));
+    assert_eq!(response["result"]["protocolVersion"], PROTOCOL_VERSION);
+  }
+
+  #[test]
+  fn reports_protocol_errors() {
+    assert!(handle_message(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).is_none());
+    assert_eq!(request("resources/list", json!({}))["error"]["code"], METHOD_NOT_FOUND);
+    assert_eq!(request("tools/call", json!({ "arguments": {} }))["error"]["code"], INVALID_PARAMS);
+    let tools = request("tools/list", json!({}));
+    assert_eq!(tools["result"]["tools"][1]["inputSchema"]["required"], json!(["group_by"]));
+  }
+
+  #[test]
+  fn answers_tool_calls_with_structured_content() {
+    let entry = |timestamp: &str, tool: &str, cost: f64| UsageEntry {
+      timestamp: timestamp.to_string(),
+      tool: tool.to_string(),
+      model: "claude-sonnet-4".to_string(),
+      input_tokens: 100,
+      total_tokens: 100,
+      cost,
+      ..Default::default()
+    };
+    let entries = [
+      entry("2025-03-01T12:00:00Z", "Claude", 1.0),
+      entry("2025-03-05T12:00:00Z", "Claude", 2.0),
+      entry("2025-03-05T12:00:00Z", "OpenCode", 4.0),
+    ];
+    let call = |name: &str, args: Value| tool_result(run_tool(name, args, &entries, "EUR"));
+
+    let summary = call("get_usage_summary", json!({ "tool": "Claude", "since": "2025-03-02" }));
+    assert_eq!(summary["structuredContent"]["requests"], 1);
+    assert_eq!(summary["structuredContent"]["cost"], 2.0);
+    assert_eq!(summary["structuredContent"]["currency"], "EUR");
+    let text: Value = serde_json::from_str(summary["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(text, summary["structuredContent"]);
+
+    let by_tool = call("get_usage_aggregates", json!({ "group_by": "tool" }));
+    let rows = by_tool["structuredContent"]["rows"].as_array().unwrap();
+    assert_eq!(rows.len(), 2);
+    assert!(by_tool.get("isError").is_none());
+
+    for (name, args) in [
+      ("get_usage_summary", json!({ "since": "March" })),
+      ("get_usage_aggregates", json!({ "group_by": "hour" })),
+      ("get_usage_forecast", json!({})),
+    ] {
+      let result = call(name, args);
+      assert_eq!(result["isError"], true, "{name}");
+      assert!(result.get("structuredContent").is_none());
+    }
+  }
+}
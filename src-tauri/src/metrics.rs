@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::aggregate::Totals;
+use crate::sources::InputSemantics;
+use crate::UsageEntry;
+
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+fn escape_label(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n")
+}
+
+/// Adds an entry to its (tool, model) totals with `input_tokens` counting
+/// uncached input only, since Codex and Gemini include cache reads in it and
+/// Claude and OpenCode do not.
+pub fn add_to_totals(
+  totals: &mut BTreeMap<(String, String), Totals>,
+  e: &UsageEntry,
+  semantics: &mut InputSemantics,
+) {
+  let t = totals.entry((e.tool.clone(), e.model.clone())).or_default();
+  t.add(e);
+  if semantics.includes_cache(&e.tool) {
+    t.input_tokens = t.input_tokens.saturating_sub(e.cache_read_tokens.min(e.input_tokens));
+  }
+}
+
+/// Totals per (tool, model), in a stable order, see [`add_to_totals`].
+pub fn totals_by_tool_model(entries: &[UsageEntry]) -> BTreeMap<(String, String), Totals> {
+  let mut semantics = InputSemantics::default();
+  let mut out = BTreeMap::new();
+  for e in entries {
+    add_to_totals(&mut out, e, &mut semantics);
+  }
+  out
+}
+
+/// Renders this machine's usage in the Prometheus text exposition format;
+/// entries imported from other machines are left out. Token and request
+/// counters only grow between incremental scans; a full rescan may reset them,
+/// which Prometheus handles as a counter reset. Cost is a gauge because
+/// changing the cost mode can lower it.
+pub fn render_prometheus(entries: &[UsageEntry]) -> String {
+  let local: Vec<UsageEntry> = entries.iter().filter(|e| e.machine_id.is_none()).cloned().collect();
+  let totals = totals_by_tool_model(&local);
+  let mut out = String::new();
+
+  let _ = writeln!(out, "# HELP token_viewer_tokens_total Tokens recorded in local agent logs.");
+  let _ = writeln!(out, "# TYPE token_viewer_tokens_total counter");
+  for ((tool, model), t) in &totals {
+    let (tool, model) = (escape_label(tool), escape_label(model));
+    for (kind, value) in [
+      ("input", t.input_tokens),
+      ("output", t.output_tokens),
+      ("cache_read", t.cache_read_tokens),
+      ("cache_write", t.cache_write_tokens),
+    ] {
+      let _ = writeln!(
+        out,
+        "token_viewer_tokens_total{{tool=\"{tool}\",model=\"{model}\",kind=\"{kind}\"}} {value}"
+      );
+    }
+  }
+
+  let _ = writeln!(out, "# HELP token_viewer_requests_total Requests with recorded usage.");
+  let _ = writeln!(out, "# TYPE token_viewer_requests_total counter");
+  for ((tool, model), t) in &totals {
+    let _ = writeln!(
+      out,
+      "token_viewer_requests_total{{tool=\"{}\",model=\"{}\"}} {}",
+      escape_label(tool),
+      escape_label(model),
+      t.requests
+    );
+  }
+
+  let _ = writeln!(out, "# HELP token_viewer_cost_usd Estimated or logged cost in US dollars.");
+  let _ = writeln!(out, "# TYPE token_viewer_cost_usd gauge");
+  for ((tool, model), t) in &totals {
+    let _ = writeln!(
+      out,
+      "token_viewer_cost_usd{{tool=\"{}\",model=\"{}\"}} {}",
+      escape_label(tool),
+      escape_label(model),
+      t.cost
+    );
+  }
+
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(tool: &str, model: &str, input: u64, cache_read: u64, cost: f64) -> UsageEntry {
+    UsageEntry {
+      timestamp: "2025-01-01T00:00:00+00:00".to_string(),
+      tool: tool.to_string(),
+      model: model.to_string(),
+      input_tokens: input,
+      output_tokens: 10,
+      cache_read_tokens: cache_read,
+      total_tokens: input + 10,
+      cost,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn escapes_label_values() {
+    assert_eq!(escape_label("a\\b\"c\nd"), "a\\\\b\\\"c\\nd");
+    let out = render_prometheus(&[entry("Claude", "odd\"model\\\n", 1, 0, 0.0)]);
+    assert!(out.contains("token_viewer_requests_total{tool=\"Claude\",model=\"odd\\\"model\\\\\\n\"} 1\n"));
+  }
+
+  #[test]
+  fn totals_count_uncached_input_for_every_tool() {
+    let out = render_prometheus(&[
+      // Codex input already includes its 60 cache reads.
+      entry("Codex", "gpt-5", 100, 60, 0.25),
+      entry("Codex", "gpt-5", 50, 0, 0.5),
+      // Claude reports cache reads separately.
+      entry("Claude", "claude-sonnet-4", 100, 60, 1.0),
+    ]);
+    for line in [
+      "token_viewer_tokens_total{tool=\"Codex\",model=\"gpt-5\",kind=\"input\"} 90",
+      "token_viewer_tokens_total{tool=\"Codex\",model=\"gpt-5\",kind=\"cache_read\"} 60",
+      "token_viewer_tokens_total{tool=\"Codex\",model=\"gpt-5\",kind=\"output\"} 20",
+      "token_viewer_requests_total{tool=\"Codex\",model=\"gpt-5\"} 2",
+      "token_viewer_cost_usd{tool=\"Codex\",model=\"gpt-5\"} 0.75",
+      "token_viewer_tokens_total{tool=\"Claude\",model=\"claude-sonnet-4\",kind=\"input\"} 100",
+      "token_viewer_tokens_total{tool=\"Claude\",model=\"claude-sonnet-4\",kind=\"cache_read\"} 60",
+      "# TYPE token_viewer_cost_usd gauge",
+    ] {
+      assert!(out.lines().any(|l| l == line), "missing {line} in\n{out}");
+    }
+  }
+
+  #[test]
+  fn leaves_out_imported_entries() {
+    let imported = UsageEntry {
+      machine_id: Some("laptop".to_string()),
+      ..entry("Codex", "gpt-5", 100, 0, 1.0)
+    };
+    let out = render_prometheus(&[entry("Claude", "claude-sonnet-4", 1, 0, 0.5), imported]);
+    assert!(!out.contains("gpt-5"));
+    assert!(out.contains("token_viewer_cost_usd{tool=\"Claude\",model=\"claude-sonnet-4\"} 0.5"));
+  }
+}
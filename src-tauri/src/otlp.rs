@@ -0,0 +1,358 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::aggregate::Totals;
+use crate::metrics::{add_to_totals, totals_by_tool_model};
+use crate::sources::InputSemantics;
+use crate::{host_name, read_config, user_name, write_config, UsageEntry};
+
+const OTLP_SETTINGS_FILE: &str = "otlp.json";
+const DEFAULT_INTERVAL_SECS: u64 = 60;
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn default_interval_secs() -> u64 {
+  DEFAULT_INTERVAL_SECS
+}
+
+/// Optional push of cumulative usage metrics to an OTLP/HTTP collector.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OtlpSettings {
+  #[serde(default)]
+  pub enabled: bool,
+  /// Collector base URL, e.g. `http://localhost:4318`; `/v1/metrics` is appended.
+  #[serde(default)]
+  pub endpoint: String,
+  /// Extra request headers, e.g. for collector authentication.
+  #[serde(default)]
+  pub headers: HashMap<String, String>,
+  #[serde(default = "default_interval_secs")]
+  pub interval_secs: u64,
+}
+
+impl Default for OtlpSettings {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      endpoint: String::new(),
+      headers: HashMap::new(),
+      interval_secs: DEFAULT_INTERVAL_SECS,
+    }
+  }
+}
+
+struct ExporterState {
+  settings: OtlpSettings,
+  start_time_unix_nano: u128,
+  totals: BTreeMap<(String, String), Totals>,
+  /// Set when totals changed since the last successful push.
+  dirty: bool,
+}
+
+static EXPORTER: Lazy<Mutex<ExporterState>> = Lazy::new(|| {
+  Mutex::new(ExporterState {
+    settings: load_settings(),
+    start_time_unix_nano: unix_nanos(),
+    totals: BTreeMap::new(),
+    dirty: false,
+  })
+});
+
+fn unix_nanos() -> u128 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_nanos())
+    .unwrap_or_default()
+}
+
+pub fn load_settings() -> OtlpSettings {
+  read_config(OTLP_SETTINGS_FILE).unwrap_or_default()
+}
+
+pub fn apply_settings(settings: OtlpSettings) -> Result<(), String> {
+  if settings.enabled {
+    let endpoint = settings.endpoint.trim();
+    if !(endpoint.starts_with("http://") || endpoint.starts_with("https://")) {
+      return Err("OTLP endpoint must be an http(s) URL".to_string());
+    }
+  }
+  if settings.interval_secs == 0 {
+    return Err("push interval must be at least one second".to_string());
+  }
+  write_config(OTLP_SETTINGS_FILE, &settings)?;
+  if let Ok(mut state) = EXPORTER.lock() {
+    state.settings = settings;
+    state.dirty = true;
+  }
+  Ok(())
+}
+
+impl ExporterState {
+  fn record(&mut self, new_entries: &[UsageEntry]) {
+    if new_entries.is_empty() {
+      return;
+    }
+    let mut semantics = InputSemantics::default();
+    for e in new_entries.iter().filter(|e| e.machine_id.is_none()) {
+      add_to_totals(&mut self.totals, e, &mut semantics);
+    }
+    self.dirty = true;
+  }
+
+  fn record_full(&mut self, entries: &[UsageEntry]) {
+    let local: Vec<UsageEntry> = entries.iter().filter(|e| e.machine_id.is_none()).cloned().collect();
+    self.totals = totals_by_tool_model(&local);
+    self.start_time_unix_nano = unix_nanos();
+    self.dirty = true;
+  }
+}
+
+/// Adds newly scanned entries to the cumulative totals. Only this machine's
+/// usage is exported; imported entries are skipped.
+pub fn record(new_entries: &[UsageEntry]) {
+  if let Ok(mut state) = EXPORTER.lock() {
+    state.record(new_entries);
+  }
+}
+
+/// Replaces the totals after a full rescan or a change to how costs are
+/// computed, counting only this machine's entries. The new totals can be lower
+/// than those already pushed, so they start a new cumulative series.
+pub fn record_full(entries: &[UsageEntry]) {
+  if let Ok(mut state) = EXPORTER.lock() {
+    state.record_full(entries);
+  }
+}
+
+/// Spawns the background thread that pushes metrics every `interval_secs`.
+pub fn start_exporter() {
+  std::thread::spawn(|| {
+    let mut last_push = Instant::now();
+    loop {
+      std::thread::sleep(Duration::from_secs(1));
+      let payload = {
+        let Ok(mut state) = EXPORTER.lock() else {
+          return;
+        };
+        let due = last_push.elapsed() >= Duration::from_secs(state.settings.interval_secs);
+        if !state.settings.enabled || !state.dirty || !due {
+          continue;
+        }
+        state.dirty = false;
+        (state.settings.clone(), build_payload(&state.totals, state.start_time_unix_nano, unix_nanos()))
+      };
+      last_push = Instant::now();
+      let (settings, body) = payload;
+      if let Err(e) = push(&settings, &body) {
+        log::warn!("Failed to push OTLP metrics: {e}");
+        if let Ok(mut state) = EXPORTER.lock() {
+          state.dirty = true;
+        }
+      }
+    }
+  });
+}
+
+fn push(settings: &OtlpSettings, body: &Value) -> Result<(), String> {
+  let url = format!("{}/v1/metrics", settings.endpoint.trim().trim_end_matches('/'));
+  let client = reqwest::blocking::Client::builder()
+    .timeout(PUSH_TIMEOUT)
+    .build()
+    .map_err(|e| e.to_string())?;
+  let mut request = client.post(&url).json(body);
+  for (name, value) in &settings.headers {
+    request = request.header(name, value);
+  }
+  let resp = request.send().map_err(|e| e.to_string())?;
+  if resp.status().is_success() {
+    Ok(())
+  } else {
+    Err(format!("collector answered {}", resp.status()))
+  }
+}
+
+fn string_attr(key: &str, value: &str) -> Value {
+  json!({ "key": key, "value": { "stringValue": value } })
+}
+
+fn sum_metric(name: &str, description: &str, unit: &str, data_points: Vec<Value>) -> Value {
+  json!({
+    "name": name,
+    "description": description,
+    "unit": unit,
+    "sum": {
+      // AGGREGATION_TEMPORALITY_CUMULATIVE
+      "aggregationTemporality": 2,
+      "isMonotonic": true,
+      "dataPoints": data_points,
+    }
+  })
+}
+
+/// Builds an OTLP/HTTP JSON `ExportMetricsServiceRequest` with cumulative sums.
+pub fn build_payload(
+  totals: &BTreeMap<(String, String), Totals>,
+  start_time_unix_nano: u128,
+  time_unix_nano: u128,
+) -> Value {
+  let start = start_time_unix_nano.to_string();
+  let now = time_unix_nano.to_string();
+  let point = |attributes: Vec<Value>, value: (&str, Value)| {
+    let mut p = json!({
+      "attributes": attributes,
+      "startTimeUnixNano": start,
+      "timeUnixNano": now,
+    });
+    p[value.0] = value.1;
+    p
+  };
+
+  let mut tokens = Vec::new();
+  let mut requests = Vec::new();
+  let mut cost = Vec::new();
+  for ((tool, model), t) in totals {
+    let labels = || vec![string_attr("tool", tool), string_attr("model", model)];
+    for (kind, value) in [
+      ("input", t.input_tokens),
+      ("output", t.output_tokens),
+      ("cache_read", t.cache_read_tokens),
+      ("cache_write", t.cache_write_tokens),
+    ] {
+      let mut attributes = labels();
+      attributes.push(string_attr("kind", kind));
+      tokens.push(point(attributes, ("asInt", json!(value.to_string()))));
+    }
+    requests.push(point(labels(), ("asInt", json!(t.requests.to_string()))));
+    cost.push(point(labels(), ("asDouble", json!(t.cost))));
+  }
+
+  json!({
+    "resourceMetrics": [{
+      "resource": {
+        "attributes": [
+          string_attr("service.name", "token-viewer"),
+          string_attr("host.name", &host_name()),
+          string_attr("user.name", &user_name()),
+        ]
+      },
+      "scopeMetrics": [{
+        "scope": { "name": "token-viewer", "version": env!("CARGO_PKG_VERSION") },
+        "metrics": [
+          sum_metric("token_viewer.tokens", "Tokens recorded in local agent logs.", "{token}", tokens),
+          sum_metric("token_viewer.requests", "Requests with recorded usage.", "{request}", requests),
+          sum_metric("token_viewer.cost", "Estimated or logged cost.", "USD", cost),
+        ]
+      }]
+    }]
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(tool: &str, model: &str, input: u64, output: u64, cost: f64) -> UsageEntry {
+    UsageEntry {
+      timestamp: "2025-01-01T00:00:00+00:00".to_string(),
+      tool: tool.to_string(),
+      model: model.to_string(),
+      input_tokens: input,
+      output_tokens: output,
+      total_tokens: input + output,
+      cost,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn pushes_cumulative_sums_to_local_collector() {
+    let collector = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let port = collector.server_addr().to_ip().unwrap().port();
+
+    let totals = totals_by_tool_model(&[
+      entry("Claude", "claude-sonnet-4", 100, 10, 0.5),
+      entry("Claude", "claude-sonnet-4", 50, 5, 0.25),
+    ]);
+    let settings = OtlpSettings {
+      enabled: true,
+      endpoint: format!("http://127.0.0.1:{port}/"),
+      headers: HashMap::from([("x-api-key".to_string(), "secret".to_string())]),
+      interval_secs: 1,
+    };
+    let body = build_payload(&totals, 1, 2);
+
+    let receiver = std::thread::spawn(move || {
+      let mut request = collector.recv().unwrap();
+      let url = request.url().to_string();
+      let key = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("x-api-key"))
+        .map(|h| h.value.to_string());
+      let mut raw = String::new();
+      request.as_reader().read_to_string(&mut raw).unwrap();
+      request.respond(tiny_http::Response::empty(200)).unwrap();
+      (url, key, raw)
+    });
+
+    push(&settings, &body).unwrap();
+    let (url, key, raw) = receiver.join().unwrap();
+    assert_eq!(url, "/v1/metrics");
+    assert_eq!(key.as_deref(), Some("secret"));
+
+    let sent: Value = serde_json::from_str(&raw).unwrap();
+    let metrics = &sent["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+    let tokens = &metrics[0]["sum"];
+    assert_eq!(tokens["aggregationTemporality"], 2);
+    assert_eq!(tokens["dataPoints"][0]["asInt"], "150");
+    assert_eq!(tokens["dataPoints"][1]["asInt"], "15");
+    assert_eq!(metrics[1]["sum"]["dataPoints"][0]["asInt"], "2");
+    assert_eq!(metrics[2]["sum"]["dataPoints"][0]["asDouble"], 0.75);
+
+    let resource = &sent["resourceMetrics"][0]["resource"]["attributes"];
+    let keys: Vec<_> = resource.as_array().unwrap().iter().map(|a| a["key"].clone()).collect();
+    assert!(keys.contains(&json!("host.name")) && keys.contains(&json!("user.name")));
+  }
+
+  #[test]
+  fn rebuilt_totals_start_a_new_series() {
+    let mut state = ExporterState {
+      settings: OtlpSettings::default(),
+      start_time_unix_nano: 0,
+      totals: BTreeMap::new(),
+      dirty: false,
+    };
+    let mut start = |entries: &[UsageEntry]| {
+      state.record_full(entries);
+      state.start_time_unix_nano
+    };
+    let first = start(&[entry("Claude", "claude-sonnet-4", 100, 10, 0.5)]);
+    std::thread::sleep(Duration::from_millis(2));
+    // Lower totals, e.g. after switching the cost mode, must not continue the old sum.
+    let second = start(&[entry("Claude", "claude-sonnet-4", 100, 10, 0.1)]);
+    assert!(second > first);
+  }
+
+  #[test]
+  fn records_only_local_entries() {
+    let mut state = ExporterState {
+      settings: OtlpSettings::default(),
+      start_time_unix_nano: 0,
+      totals: BTreeMap::new(),
+      dirty: false,
+    };
+    let imported = UsageEntry {
+      machine_id: Some("laptop".to_string()),
+      ..entry("Claude", "claude-sonnet-4", 1000, 100, 5.0)
+    };
+    let local = entry("Claude", "claude-sonnet-4", 100, 10, 0.5);
+
+    state.record_full(&[local.clone(), imported.clone()]);
+    state.record(&[local, imported]);
+    let totals = &state.totals[&("Claude".to_string(), "claude-sonnet-4".to_string())];
+    assert_eq!((totals.requests, totals.input_tokens, totals.cost), (2, 200, 1.0));
+  }
+}
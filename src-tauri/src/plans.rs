@@ -0,0 +1,208 @@
+use chrono::{Duration, Local, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::cost_mode::calculated_cost;
+use crate::currency::Converter;
+use crate::sources::InputSemantics;
+use crate::{read_config, write_config, PricingTable, UsageEntry};
+
+const PLANS_FILE: &str = "plans.json";
+
+/// A flat-fee subscription (e.g. Claude Max, ChatGPT Pro) billed monthly from
+/// `start_date`, covering usage of the listed tools.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Plan {
+  pub id: String,
+  #[serde(default)]
+  pub name: String,
+  /// Fee in USD per billing month.
+  pub monthly_fee: f64,
+  /// Tools whose usage the plan covers, matched case-insensitively.
+  pub tools: Vec<String>,
+  /// First day of the first billing period; later periods start on the same day of the month.
+  pub start_date: NaiveDate,
+  /// Last day the plan was active, if cancelled.
+  #[serde(default)]
+  pub end_date: Option<NaiveDate>,
+}
+
+impl Plan {
+  fn validate(&self) -> Result<(), String> {
+    if self.id.trim().is_empty() {
+      return Err("plan is missing an id".to_string());
+    }
+    if !self.monthly_fee.is_finite() || self.monthly_fee < 0.0 {
+      return Err(format!("plan '{}' needs a non-negative monthly fee", self.id));
+    }
+    if self.tools.iter().all(|t| t.trim().is_empty()) {
+      return Err(format!("plan '{}' covers no tools", self.id));
+    }
+    if self.end_date.is_some_and(|end| end < self.start_date) {
+      return Err(format!("plan '{}' ends before it starts", self.id));
+    }
+    Ok(())
+  }
+
+  fn covers(&self, entry: &UsageEntry) -> bool {
+    self.tools.iter().any(|t| entry.tool.eq_ignore_ascii_case(t.trim()))
+  }
+
+  fn display_name(&self) -> &str {
+    if self.name.trim().is_empty() { &self.id } else { &self.name }
+  }
+
+  /// Billing periods `[start, end)` as local dates, from the start date up to
+  /// the period containing `last` (or the end date, if earlier).
+  fn periods(&self, last: NaiveDate) -> Vec<(NaiveDate, NaiveDate)> {
+    let last = self.end_date.map_or(last, |end| end.min(last));
+    let mut periods = Vec::new();
+    for i in 0.. {
+      // Offsets from the start date keep the billing day after short months (Jan 31 -> Feb 28 -> Mar 31).
+      let (Some(start), Some(end)) = (
+        self.start_date.checked_add_months(Months::new(i)),
+        self.start_date.checked_add_months(Months::new(i + 1)),
+      ) else {
+        break;
+      };
+      if start > last {
+        break;
+      }
+      periods.push((start, end));
+    }
+    periods
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanPeriodValue {
+  pub plan_id: String,
+  pub plan_name: String,
+  pub period_start: NaiveDate,
+  /// Exclusive.
+  pub period_end: NaiveDate,
+  pub is_current: bool,
+  /// What the subscription cost for the period.
+  pub actual_cost: f64,
+  /// What the covered usage would have cost at API prices.
+  pub api_equivalent_cost: f64,
+  /// `api_equivalent_cost / actual_cost`; above 1 means the plan was cheaper than the API.
+  pub value_ratio: Option<f64>,
+  pub requests: u64,
+  pub total_tokens: u64,
+}
+
+pub fn load_plans() -> Vec<Plan> {
+  read_config(PLANS_FILE).unwrap_or_default()
+}
+
+pub fn save_plans(plans: &[Plan]) -> Result<(), String> {
+  let mut ids = HashSet::new();
+  for plan in plans {
+    plan.validate()?;
+    if !ids.insert(plan.id.as_str()) {
+      return Err(format!("duplicate plan id '{}'", plan.id));
+    }
+  }
+  write_config(PLANS_FILE, plans)
+}
+
+/// Actual vs API-equivalent cost for every billing period of every plan that
+/// overlaps `[since, until]`, oldest first within each plan. The API-equivalent
+/// cost is always calculated from tokens, whatever the cost mode, since logged
+/// costs of subscription usage are often zero. Amounts are in the converter's
+/// currency; fees convert at the rate of the period's first day and usage at
+/// the rate of each request's day.
+pub fn compare(
+  plans: &[Plan],
+  entries: &[UsageEntry],
+  since: Option<NaiveDate>,
+  until: Option<NaiveDate>,
+  converter: &Converter,
+  pricing: &PricingTable,
+) -> Vec<PlanPeriodValue> {
+  let today = Local::now().date_naive();
+  let mut semantics = InputSemantics::default();
+  let mut out = Vec::new();
+  for plan in plans {
+    let covered: Vec<(NaiveDate, &UsageEntry, f64)> = entries
+      .iter()
+      .filter(|e| plan.covers(e))
+      .filter_map(|e| {
+        let date = e.local_time()?.date_naive();
+        let usd = calculated_cost(e, semantics.includes_cache(&e.tool), pricing);
+        Some((date, e, converter.convert(usd, Some(date))))
+      })
+      .collect();
+
+    for (start, end) in plan.periods(until.unwrap_or(today)) {
+      if since.is_some_and(|s| end <= s) {
+        continue;
+      }
+      // Usage after a cancellation is not covered, though the last period is billed in full.
+      let covered_end = plan.end_date.map_or(end, |d| end.min(d + Duration::days(1)));
+      let mut api_equivalent_cost = 0.0;
+      let mut requests = 0;
+      let mut total_tokens = 0u64;
+      for (date, e, cost) in &covered {
+        if *date < start || *date >= covered_end {
+          continue;
+        }
+        api_equivalent_cost += cost;
+        requests += 1;
+        total_tokens = total_tokens.saturating_add(e.total_tokens);
+      }
+      let actual_cost = converter.convert(plan.monthly_fee, Some(start));
+      out.push(PlanPeriodValue {
+        plan_id: plan.id.clone(),
+        plan_name: plan.display_name().to_string(),
+        period_start: start,
+        period_end: end,
+        is_current: (start..end).contains(&today),
+        actual_cost,
+        api_equivalent_cost,
+        value_ratio: (actual_cost > 0.0).then(|| api_equivalent_cost / actual_cost),
+        requests,
+        total_tokens,
+      });
+    }
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::PricingInfo;
+  use std::collections::HashMap;
+
+  #[test]
+  fn api_equivalent_cost_ignores_logged_costs() {
+    let plan = Plan {
+      id: "max".to_string(),
+      name: String::new(),
+      monthly_fee: 100.0,
+      tools: vec!["Claude".to_string()],
+      start_date: NaiveDate::from_ymd_opt(2025, 3, 1).unwrap(),
+      end_date: Some(NaiveDate::from_ymd_opt(2025, 3, 31).unwrap()),
+    };
+    let entry = UsageEntry {
+      timestamp: "2025-03-10T12:00:00+00:00".to_string(),
+      tool: "Claude".to_string(),
+      model: "claude-sonnet-4".to_string(),
+      input_tokens: 1000,
+      total_tokens: 1000,
+      cost: 7.0,
+      logged_cost: Some(7.0),
+      ..Default::default()
+    };
+    let rates = PricingInfo { input_cost_per_token: 1e-3, ..Default::default() };
+    let pricing = PricingTable::new(HashMap::from([("claude-sonnet-4".to_string(), rates)]));
+
+    let periods = compare(&[plan], &[entry], None, None, &Converter::usd(), &pricing);
+    assert_eq!(periods.len(), 1);
+    assert_eq!(periods[0].requests, 1);
+    assert!((periods[0].api_equivalent_cost - 1.0).abs() < 1e-9);
+    assert_eq!(periods[0].actual_cost, 100.0);
+  }
+}
@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::{home_glob_prefix, read_config, write_config};
+
+const PROVIDERS_FILE: &str = "providers.json";
+
+/// Cloud platform a model was served through, when it is not the model
+/// vendor's own API. Prices differ per platform and region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Provider {
+  Bedrock,
+  VertexAi,
+  Azure,
+}
+
+impl Provider {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Provider::Bedrock => "bedrock",
+      Provider::VertexAi => "vertex_ai",
+      Provider::Azure => "azure",
+    }
+  }
+
+  /// LiteLLM key prefixes priced for this provider, most specific first.
+  pub fn key_prefixes(self) -> &'static [&'static str] {
+    match self {
+      // Bedrock model ids are listed both with and without the `bedrock/` prefix,
+      // and cross-region inference profiles add a geography in front.
+      Provider::Bedrock => &[
+        "bedrock/",
+        "bedrock/anthropic.",
+        "anthropic.",
+        "us.anthropic.",
+        "eu.anthropic.",
+        "apac.anthropic.",
+        "global.anthropic.",
+      ],
+      Provider::VertexAi => &["vertex_ai/"],
+      Provider::Azure => &["azure/"],
+    }
+  }
+}
+
+/// Tags entries from a source, or from log files under `path`, with a provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderRule {
+  /// Tool name, matched case-insensitively.
+  pub tool: String,
+  /// Only log files under this directory; `~` expands to the home directory.
+  #[serde(default)]
+  pub path: Option<String>,
+  pub provider: Provider,
+}
+
+impl ProviderRule {
+  fn root(&self) -> Option<String> {
+    let path = self.path.as_deref()?.trim().replace('\\', "/");
+    let path = match path.strip_prefix('~') {
+      Some(rest) => format!("{}{rest}", home_glob_prefix()?),
+      None => path,
+    };
+    Some(path.trim_end_matches('/').to_string())
+  }
+
+  fn validate(&self) -> Result<(), String> {
+    if self.tool.trim().is_empty() {
+      return Err("provider rule is missing a tool name".to_string());
+    }
+    if self.path.as_deref().is_some_and(|p| p.trim().is_empty()) {
+      return Err(format!("provider rule for '{}' has an empty path", self.tool));
+    }
+    Ok(())
+  }
+}
+
+pub fn load_rules() -> Vec<ProviderRule> {
+  read_config(PROVIDERS_FILE).unwrap_or_default()
+}
+
+pub fn save_rules(rules: &[ProviderRule]) -> Result<(), String> {
+  for rule in rules {
+    rule.validate()?;
+  }
+  write_config(PROVIDERS_FILE, rules)
+}
+
+/// Provider for a log file of `tool`. A rule with a path beats one without,
+/// and the longest matching path wins.
+pub fn provider_for(rules: &[ProviderRule], tool: &str, file: &Path) -> Option<Provider> {
+  let file = file.to_string_lossy().replace('\\', "/");
+  rules
+    .iter()
+    .filter(|r| r.tool.trim().eq_ignore_ascii_case(tool))
+    .filter_map(|r| match r.root() {
+      None if r.path.is_none() => Some((0, r.provider)),
+      Some(root) if file.strip_prefix(&root).is_some_and(|rest| rest.starts_with('/')) => {
+        Some((root.len() + 1, r.provider))
+      }
+      _ => None,
+    })
+    .max_by_key(|(specificity, _)| *specificity)
+    .map(|(_, provider)| provider)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rule(tool: &str, path: Option<&str>, provider: Provider) -> ProviderRule {
+    ProviderRule { tool: tool.to_string(), path: path.map(str::to_string), provider }
+  }
+
+  #[test]
+  fn path_rules_win_and_longest_path_wins() {
+    let rules = [
+      rule("claude", None, Provider::Azure),
+      rule("Claude", Some("/logs/work"), Provider::Bedrock),
+      rule("Claude", Some("/logs/work/gcp/"), Provider::VertexAi),
+    ];
+    let find = |tool: &str, file: &str| provider_for(&rules, tool, Path::new(file));
+
+    assert_eq!(find("Claude", "/logs/home/a.jsonl"), Some(Provider::Azure));
+    assert_eq!(find("Claude", "/logs/work/a.jsonl"), Some(Provider::Bedrock));
+    assert_eq!(find("Claude", "/logs/work/gcp/a.jsonl"), Some(Provider::VertexAi));
+    // A sibling directory sharing the prefix is not under the rule's path.
+    assert_eq!(find("Claude", "/logs/workshop/a.jsonl"), Some(Provider::Azure));
+    assert_eq!(find("Codex", "/logs/work/a.jsonl"), None);
+  }
+
+  #[test]
+  fn path_rules_without_a_tool_wide_rule_only_match_their_path() {
+    let rules = [rule("Codex", Some(r"C:\logs\azure"), Provider::Azure)];
+    let find = |file: &str| provider_for(&rules, "codex", Path::new(file));
+
+    assert_eq!(find(r"C:\logs\azure\rollout.jsonl"), Some(Provider::Azure));
+    assert_eq!(find("C:/logs/other/rollout.jsonl"), None);
+  }
+}
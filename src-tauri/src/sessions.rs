@@ -0,0 +1,221 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::aggregate::{EntryFilter, Totals};
+use crate::UsageEntry;
+
+/// Page size when the caller does not ask for one.
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionSort {
+  #[default]
+  Cost,
+  Tokens,
+  Requests,
+  Start,
+  End,
+  Duration,
+}
+
+impl SessionSort {
+  pub fn parse(s: &str) -> Option<Self> {
+    serde_json::from_value(serde_json::Value::String(s.to_lowercase())).ok()
+  }
+}
+
+/// Usage of one conversation: a Claude or Gemini session id, a Codex session
+/// file or an OpenCode session.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSummary {
+  pub session_id: String,
+  pub tool: String,
+  /// Set for sessions imported from another machine's bundle.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub machine_id: Option<String>,
+  pub project: Option<String>,
+  /// First and last request, RFC 3339.
+  pub start: String,
+  pub end: String,
+  pub duration_seconds: i64,
+  pub models: Vec<String>,
+  #[serde(flatten)]
+  pub totals: Totals,
+  /// The split of `totals` between the conversation itself and its subagents.
+  pub main_thread: Totals,
+  pub subagents: Totals,
+  /// Distinct subagent ids seen; subagent requests without one count once.
+  pub subagent_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionPage {
+  /// Sessions matching the filter, before paging.
+  pub total: usize,
+  pub offset: usize,
+  pub sessions: Vec<SessionSummary>,
+}
+
+#[derive(Default)]
+struct Session {
+  start: Option<DateTime<Utc>>,
+  end: Option<DateTime<Utc>>,
+  project: Option<String>,
+  models: BTreeSet<String>,
+  totals: Totals,
+  main_thread: Totals,
+  subagents: Totals,
+  agents: BTreeSet<Option<String>>,
+}
+
+/// Groups entries with a session id by tool, machine and session, sorts them
+/// by `sort` (largest or latest first unless `ascending`) and returns the page
+/// starting at `offset`.
+pub fn list_sessions(
+  entries: &[UsageEntry],
+  filter: &EntryFilter,
+  sort: SessionSort,
+  ascending: bool,
+  offset: usize,
+  limit: usize,
+) -> SessionPage {
+  let mut groups: HashMap<(&str, Option<&str>, &str), Session> = HashMap::new();
+  for e in entries.iter().filter(|e| filter.matches(e)) {
+    let (Some(id), Some(time)) = (e.session_id.as_deref(), e.utc_time()) else {
+      continue;
+    };
+    let session = groups.entry((e.tool.as_str(), e.machine_id.as_deref(), id)).or_default();
+    // The project of the earliest request, where the conversation was started.
+    if session.start.map_or(true, |s| time < s) {
+      session.start = Some(time);
+      session.project = e.project.clone().or(session.project.take());
+    } else if session.project.is_none() {
+      session.project = e.project.clone();
+    }
+    if session.end.map_or(true, |end| time > end) {
+      session.end = Some(time);
+    }
+    session.models.insert(e.model.clone());
+    session.totals.add(e);
+    if e.sidechain {
+      session.subagents.add(e);
+      session.agents.insert(e.agent_id.clone());
+    } else {
+      session.main_thread.add(e);
+    }
+  }
+
+  let mut sessions: Vec<SessionSummary> = groups
+    .into_iter()
+    .filter_map(|((tool, machine_id, id), s)| {
+      let (start, end) = (s.start?, s.end?);
+      Some(SessionSummary {
+        session_id: id.to_string(),
+        tool: tool.to_string(),
+        machine_id: machine_id.map(str::to_string),
+        project: s.project,
+        start: start.to_rfc3339(),
+        end: end.to_rfc3339(),
+        duration_seconds: (end - start).num_seconds(),
+        models: s.models.into_iter().collect(),
+        totals: s.totals,
+        main_thread: s.main_thread,
+        subagents: s.subagents,
+        subagent_count: s.agents.len(),
+      })
+    })
+    .collect();
+
+  sessions.sort_by(|a, b| {
+    let order = match sort {
+      SessionSort::Cost => a.totals.cost.total_cmp(&b.totals.cost),
+      SessionSort::Tokens => a.totals.total_tokens.cmp(&b.totals.total_tokens),
+      SessionSort::Requests => a.totals.requests.cmp(&b.totals.requests),
+      SessionSort::Start => a.start.cmp(&b.start),
+      SessionSort::End => a.end.cmp(&b.end),
+      SessionSort::Duration => a.duration_seconds.cmp(&b.duration_seconds),
+    };
+    // Ties fall back to the session id so pages stay stable between calls.
+    let order = if ascending { order } else { order.reverse() };
+    order.then_with(|| a.session_id.cmp(&b.session_id))
+  });
+
+  let total = sessions.len();
+  SessionPage {
+    total,
+    offset,
+    sessions: sessions.into_iter().skip(offset).take(limit).collect(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(minute: u32, session: &str, model: &str, cost: f64) -> UsageEntry {
+    subagent_entry(minute, session, model, cost, None)
+  }
+
+  fn subagent_entry(minute: u32, session: &str, model: &str, cost: f64, agent: Option<&str>) -> UsageEntry {
+    UsageEntry {
+      timestamp: format!("2025-03-01T10:{minute:02}:00+00:00"),
+      tool: "Claude".to_string(),
+      model: model.to_string(),
+      input_tokens: 100,
+      output_tokens: 10,
+      total_tokens: 110,
+      cost,
+      session_id: Some(session.to_string()),
+      sidechain: agent.is_some(),
+      agent_id: agent.map(str::to_string),
+      project: Some(format!("/work/{session}")),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn groups_sorts_and_pages_sessions() {
+    let entries = vec![
+      entry(0, "a", "claude-sonnet-4", 1.0),
+      entry(30, "a", "claude-opus-4", 4.0),
+      entry(5, "b", "claude-sonnet-4", 2.0),
+      entry(10, "c", "claude-sonnet-4", 0.5),
+    ];
+
+    let page = list_sessions(&entries, &EntryFilter::default(), SessionSort::Cost, false, 0, 2);
+    assert_eq!(page.total, 3);
+    let ids: Vec<&str> = page.sessions.iter().map(|s| s.session_id.as_str()).collect();
+    assert_eq!(ids, ["a", "b"]);
+    let a = &page.sessions[0];
+    assert_eq!(a.totals.requests, 2);
+    assert_eq!(a.totals.cost, 5.0);
+    assert_eq!(a.duration_seconds, 30 * 60);
+    assert_eq!(a.models, ["claude-opus-4", "claude-sonnet-4"]);
+    assert_eq!(a.project.as_deref(), Some("/work/a"));
+
+    let next = list_sessions(&entries, &EntryFilter::default(), SessionSort::Start, true, 2, 2);
+    let ids: Vec<&str> = next.sessions.iter().map(|s| s.session_id.as_str()).collect();
+    assert_eq!(ids, ["c"]);
+  }
+
+  #[test]
+  fn splits_subagent_usage_from_the_main_thread() {
+    let entries = vec![
+      entry(0, "a", "claude-opus-4", 3.0),
+      subagent_entry(1, "a", "claude-sonnet-4", 1.0, Some("x1")),
+      subagent_entry(2, "a", "claude-sonnet-4", 0.5, Some("x2")),
+      subagent_entry(3, "a", "claude-sonnet-4", 0.5, Some("x1")),
+    ];
+
+    let page = list_sessions(&entries, &EntryFilter::default(), SessionSort::Cost, false, 0, 10);
+    assert_eq!(page.total, 1);
+    let a = &page.sessions[0];
+    assert_eq!(a.totals.cost, 5.0);
+    assert_eq!(a.main_thread.cost, 3.0);
+    assert_eq!(a.subagents.cost, 2.0);
+    assert_eq!(a.subagents.requests, 3);
+    assert_eq!(a.subagent_count, 2);
+  }
+}
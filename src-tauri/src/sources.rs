@@ -0,0 +1,1205 @@
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::custom_sources::{load_custom_sources, CustomSource};
+use crate::providers::{provider_for, ProviderRule};
+use crate::{
+  file_mtime_rfc3339, glob_paths, home_glob_prefix, normalize_epoch, normalize_timestamp,
+  total_tokens_with_cache, total_tokens_without_cache, value_f64, value_u64, ServiceTier,
+  UsageEntry,
+};
+
+/// Incremental bookkeeping kept per log file between scans.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileState {
+  /// Byte offset for JSONL logs; file length or item count for whole-file JSON logs.
+  pub offset: u64,
+  /// Last model seen in the file, for logs that only record it on context lines.
+  pub model: Option<String>,
+  /// Last cumulative counters, for logs that report running totals instead of deltas.
+  pub totals: Option<TokenCounters>,
+  /// Last working directory seen in the file, for logs that only record it on context lines.
+  #[serde(default)]
+  pub project: Option<String>,
+  /// Last service tier seen in the file, for logs that only record it on context lines.
+  #[serde(default)]
+  pub service_tier: Option<ServiceTier>,
+  /// Tool calls logged since the last token count, for logs that record them separately.
+  #[serde(default)]
+  pub tool_calls: Vec<String>,
+  /// Last message id seen in the file, for logs that repeat a message's usage
+  /// on each of its content-block lines.
+  #[serde(default)]
+  pub message_id: Option<String>,
+  /// Database rows skipped while their response was still streaming, read again on the next scan.
+  #[serde(default)]
+  pub pending: Vec<u64>,
+}
+
+/// A token reading as logged, either per request or as a running total.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenCounters {
+  pub input: u64,
+  pub output: u64,
+  pub cache_read: u64,
+  pub cache_write: u64,
+  pub total: u64,
+}
+
+impl TokenCounters {
+  /// Per-request delta from an earlier running total; the first reading is taken as-is.
+  pub fn since(&self, prev: Option<&TokenCounters>) -> TokenCounters {
+    let Some(prev) = prev else {
+      return *self;
+    };
+    TokenCounters {
+      input: self.input.saturating_sub(prev.input),
+      output: self.output.saturating_sub(prev.output),
+      cache_read: self.cache_read.saturating_sub(prev.cache_read),
+      cache_write: self.cache_write.saturating_sub(prev.cache_write),
+      total: self.total.saturating_sub(prev.total),
+    }
+  }
+}
+
+/// A local log source that can be scanned into `UsageEntry` rows.
+pub trait UsageSource: Send + Sync {
+  /// Tool name written to `UsageEntry::tool`.
+  fn tool(&self) -> &str;
+
+  /// Directories to search, given the home directory with forward slashes.
+  fn roots(&self, home: &str) -> Vec<String>;
+
+  /// Glob patterns relative to each root.
+  fn file_patterns(&self) -> Vec<String>;
+
+  /// Parses whatever is new in `path` since `state` and advances `state`.
+  /// Entries carry the cost the tool logged, if any; they are priced by the caller.
+  fn scan_file(&self, path: &Path, state: &mut FileState) -> Vec<UsageEntry>;
+
+  /// True if `path` holds everything the source's other files logged, so the
+  /// entries already counted from them are dropped when it is first scanned.
+  fn replaces_other_files(&self, _path: &Path) -> bool {
+    false
+  }
+}
+
+/// Tool names reserved by the built-in sources.
+pub const BUILTIN_TOOLS: [&str; 4] = ["Claude", "Codex", "OpenCode", "Gemini"];
+
+/// All sources scanned by the app, in display order: built-ins first, then
+/// user-defined sources from the config directory.
+pub fn registry() -> Vec<Box<dyn UsageSource>> {
+  let mut sources: Vec<Box<dyn UsageSource>> = vec![
+    Box::new(ClaudeSource),
+    Box::new(CodexSource),
+    Box::new(OpenCodeSource),
+    Box::new(GeminiSource),
+  ];
+  for def in load_custom_sources() {
+    sources.push(Box::new(CustomSource::new(def)));
+  }
+  sources
+}
+
+/// True if `tool` logs input counts that already include cache reads
+/// (OpenAI/Gemini style) rather than reporting them separately.
+pub fn input_includes_cache_read(tool: &str) -> bool {
+  match tool {
+    "Codex" | "Gemini" => true,
+    "Claude" | "OpenCode" => false,
+    _ => load_custom_sources()
+      .iter()
+      .any(|d| d.input_includes_cache_read && d.tool.trim().eq_ignore_ascii_case(tool)),
+  }
+}
+
+/// Per-tool memo of `input_includes_cache_read`, which may read the custom source config.
+#[derive(Default)]
+pub struct InputSemantics(HashMap<String, bool>);
+
+impl InputSemantics {
+  pub fn includes_cache(&mut self, tool: &str) -> bool {
+    if let Some(v) = self.0.get(tool) {
+      return *v;
+    }
+    let v = input_includes_cache_read(tool);
+    self.0.insert(tool.to_string(), v);
+    v
+  }
+}
+
+pub fn find_source(tool: &str) -> Option<Box<dyn UsageSource>> {
+  registry()
+    .into_iter()
+    .find(|s| s.tool().eq_ignore_ascii_case(tool))
+}
+
+pub fn source_patterns(source: &dyn UsageSource) -> Vec<String> {
+  let Some(home) = home_glob_prefix() else {
+    return Vec::new();
+  };
+  let mut patterns = Vec::new();
+  for root in source.roots(&home) {
+    let root = root.trim_end_matches('/');
+    for pattern in source.file_patterns() {
+      patterns.push(format!("{root}/{pattern}"));
+    }
+  }
+  patterns
+}
+
+/// Entries found by `scan_source`.
+#[derive(Default)]
+pub struct SourceScan {
+  pub entries: Vec<UsageEntry>,
+  /// Set when a file replacing the source's other files appeared; entries
+  /// counted from the source before this scan must be dropped.
+  pub replaces_earlier: bool,
+}
+
+/// Scans every file of `source`, resuming from and updating `states` (keyed by path),
+/// and tags entries with the provider `rules` give their file.
+pub fn scan_source(
+  source: &dyn UsageSource,
+  states: &mut HashMap<String, FileState>,
+  rules: &[ProviderRule],
+) -> SourceScan {
+  let files = glob_paths(&source_patterns(source));
+  let mut out = SourceScan::default();
+
+  for path in files {
+    let key = path.to_string_lossy().to_string();
+    if !states.contains_key(&key) && source.replaces_other_files(&path) {
+      out.entries.clear();
+      out.replaces_earlier = true;
+    }
+    let state = states.entry(key).or_default();
+    let mut entries = source.scan_file(&path, state);
+    if let Some(provider) = provider_for(rules, source.tool(), &path) {
+      for e in &mut entries {
+        e.provider = Some(provider);
+      }
+    }
+    out.entries.extend(entries);
+  }
+
+  out
+}
+
+/// True if `path` shrank below the recorded offset, meaning it will be re-read
+/// from the start and any running totals kept for it are stale.
+pub fn was_truncated(path: &Path, state: &FileState) -> bool {
+  fs::metadata(path).map(|m| m.len() < state.offset).unwrap_or(false)
+}
+
+/// Calls `f` for every JSON line appended to `path` since `state.offset`, then
+/// advances the offset. Restarts from the beginning if the file was truncated.
+pub fn for_each_new_line(path: &Path, state: &mut FileState, mut f: impl FnMut(&Value)) {
+  let file_len = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+  let prev_offset = state.offset;
+
+  // Skip if no new content; reset if file was truncated
+  let start_offset = if file_len <= prev_offset && prev_offset > 0 {
+    if file_len == prev_offset { return; }
+    0 // file was truncated, re-read
+  } else {
+    prev_offset
+  };
+
+  let mut file = match File::open(path) {
+    Ok(f) => f,
+    Err(_) => return,
+  };
+
+  if start_offset > 0 && file.seek(SeekFrom::Start(start_offset)).is_err() {
+    return;
+  }
+
+  let reader = BufReader::new(&mut file);
+  for line in reader.lines().map_while(Result::ok) {
+    let line = line.trim();
+    if line.is_empty() {
+      continue;
+    }
+
+    let v: Value = match serde_json::from_str(line) {
+      Ok(v) => v,
+      Err(_) => continue,
+    };
+    f(&v);
+  }
+
+  // Update offset to current file position
+  state.offset = file.stream_position().unwrap_or(file_len);
+}
+
+/// Claude Code writes Task-tool subagent transcripts to `agent-<id>.jsonl`,
+/// next to the session logs or under `<session>/subagents/`. Returns the
+/// agent id for such a file.
+fn claude_subagent_file(path: &Path) -> Option<String> {
+  let stem = path.file_stem()?.to_str()?;
+  if let Some(id) = stem.strip_prefix("agent-") {
+    return Some(id.to_string());
+  }
+  let in_subagents = path.parent().and_then(|p| p.file_name()).is_some_and(|n| n == "subagents");
+  in_subagents.then(|| stem.to_string())
+}
+
+/// Names of the client and server tools called in a Claude message.
+fn claude_tool_calls(v: &Value) -> Vec<String> {
+  let Some(content) = v.pointer("/message/content").and_then(|c| c.as_array()) else {
+    return Vec::new();
+  };
+  content
+    .iter()
+    .filter(|c| matches!(c.get("type").and_then(|t| t.as_str()), Some("tool_use" | "server_tool_use")))
+    .filter_map(|c| c.get("name").and_then(|n| n.as_str()))
+    .map(str::to_string)
+    .collect()
+}
+
+pub struct ClaudeSource;
+
+impl UsageSource for ClaudeSource {
+  fn tool(&self) -> &str {
+    "Claude"
+  }
+
+  fn roots(&self, home: &str) -> Vec<String> {
+    vec![format!("{home}/.config/claude"), format!("{home}/.claude")]
+  }
+
+  fn file_patterns(&self) -> Vec<String> {
+    vec!["projects/**/*.jsonl".to_string()]
+  }
+
+  fn scan_file(&self, path: &Path, state: &mut FileState) -> Vec<UsageEntry> {
+    let fallback_ts = file_mtime_rfc3339(path).unwrap_or_default();
+    let file_agent = claude_subagent_file(path);
+    let mut out = Vec::new();
+    let mut last_message_id = state.message_id.take();
+
+    for_each_new_line(path, state, |v| {
+      let usage = v.get("message").and_then(|m| m.get("usage"));
+      let input_tokens = value_u64(usage.and_then(|u| u.get("input_tokens")));
+      let output_tokens = value_u64(usage.and_then(|u| u.get("output_tokens")));
+      let cache_write_tokens = value_u64(usage.and_then(|u| u.get("cache_creation_input_tokens")));
+      let cache_read_tokens = value_u64(usage.and_then(|u| u.get("cache_read_input_tokens")));
+      let total_tokens = {
+        let from_usage = value_u64(
+          usage
+            .and_then(|u| u.get("total_tokens"))
+            .or(usage.and_then(|u| u.get("totalTokens"))),
+        );
+        if from_usage > 0 {
+          from_usage
+        } else {
+          total_tokens_with_cache(
+            input_tokens,
+            output_tokens,
+            cache_read_tokens,
+            cache_write_tokens,
+          )
+        }
+      };
+      let logged_cost = value_f64(v.get("costUSD"));
+
+      if input_tokens == 0
+        && output_tokens == 0
+        && cache_write_tokens == 0
+        && cache_read_tokens == 0
+        && logged_cost == 0.0
+      {
+        return;
+      }
+
+      let timestamp =
+        normalize_timestamp(v.get("timestamp")).unwrap_or_else(|| fallback_ts.clone());
+      let model = {
+        let from_message = v
+          .get("message")
+          .and_then(|m| m.get("model"))
+          .and_then(|m| m.as_str())
+          .unwrap_or("unknown");
+        if from_message != "unknown" {
+          from_message.to_string()
+        } else {
+          v.get("model")
+            .and_then(|m| m.as_str())
+            .unwrap_or("unknown")
+            .to_string()
+        }
+      };
+
+      let service_tier = ServiceTier::from_value(usage.and_then(|u| u.get("service_tier")));
+      // Each content block of a message is its own line carrying the message's usage,
+      // so its searches are only counted on the first.
+      let message_id = v.pointer("/message/id").and_then(|id| id.as_str()).map(str::to_string);
+      let repeated = message_id.is_some() && message_id == last_message_id;
+      if message_id.is_some() {
+        last_message_id = message_id;
+      }
+      let web_search_requests = if repeated {
+        0
+      } else {
+        value_u64(usage.and_then(|u| u.pointer("/server_tool_use/web_search_requests")))
+      };
+      let sidechain = file_agent.is_some() || v.get("isSidechain").and_then(|s| s.as_bool()) == Some(true);
+      let agent_id = if sidechain {
+        v.get("agentId").and_then(|s| s.as_str()).map(str::to_string).or_else(|| file_agent.clone())
+      } else {
+        None
+      };
+
+      out.push(UsageEntry {
+        timestamp,
+        tool: self.tool().to_string(),
+        model,
+        input_tokens,
+        output_tokens,
+        cache_read_tokens,
+        cache_write_tokens,
+        total_tokens,
+        cost: logged_cost,
+        logged_cost: (logged_cost != 0.0).then_some(logged_cost),
+        provider: None,
+        service_tier,
+        session_id: v.get("sessionId").and_then(|s| s.as_str()).map(str::to_string),
+        sidechain,
+        agent_id,
+        tool_calls: claude_tool_calls(v),
+        web_search_requests,
+        project: v.get("cwd").and_then(|s| s.as_str()).map(str::to_string),
+        machine_id: None,
+      });
+    });
+
+    state.message_id = last_message_id;
+    out
+  }
+}
+
+fn extract_codex_model(v: &Value) -> Option<String> {
+  // Try payload.info.model, payload.info.model_name
+  for ptr in ["/payload/info/model", "/payload/info/model_name"] {
+    if let Some(s) = v.pointer(ptr).and_then(|m| m.as_str()) {
+      let s = s.trim();
+      if !s.is_empty() { return Some(s.to_string()); }
+    }
+  }
+  // Try payload.info.metadata.model
+  if let Some(s) = v.pointer("/payload/info/metadata/model").and_then(|m| m.as_str()) {
+    let s = s.trim();
+    if !s.is_empty() { return Some(s.to_string()); }
+  }
+  // Try payload.model
+  if let Some(s) = v.pointer("/payload/model").and_then(|m| m.as_str()) {
+    let s = s.trim();
+    if !s.is_empty() { return Some(s.to_string()); }
+  }
+  // Try payload.metadata.model
+  if let Some(s) = v.pointer("/payload/metadata/model").and_then(|m| m.as_str()) {
+    let s = s.trim();
+    if !s.is_empty() { return Some(s.to_string()); }
+  }
+  None
+}
+
+fn codex_token_counters(usage: &Value) -> TokenCounters {
+  let input = value_u64(usage.get("input_tokens"));
+  let output = value_u64(usage.get("output_tokens"));
+  let cache_read =
+    value_u64(usage.get("cached_input_tokens").or(usage.get("cache_read_input_tokens")));
+  let total = {
+    let explicit_total = value_u64(usage.get("total_tokens"));
+    if explicit_total > 0 {
+      explicit_total
+    } else {
+      total_tokens_without_cache(input, output)
+    }
+  };
+  TokenCounters { input, output, cache_read, cache_write: 0, total }
+}
+
+const CODEX_WEB_SEARCH: &str = "web_search";
+
+/// Tool named by a Codex `response_item`, if it is a call. Calls are logged
+/// before the `token_count` event of the turn that made them.
+fn codex_tool_call(v: &Value) -> Option<String> {
+  let payload = v.get("payload")?;
+  match payload.get("type").and_then(|t| t.as_str())? {
+    "function_call" | "custom_tool_call" => payload.get("name").and_then(|n| n.as_str()).map(str::to_string),
+    "local_shell_call" => Some("local_shell".to_string()),
+    "web_search_call" => Some(CODEX_WEB_SEARCH.to_string()),
+    _ => None,
+  }
+}
+
+/// Codex names session files `rollout-<time>-<uuid>.jsonl`; the uuid is the
+/// session id, and the whole stem stands in for it otherwise.
+fn codex_session_id(path: &Path) -> Option<String> {
+  let stem = path.file_stem()?.to_str()?;
+  let uuid = stem
+    .len()
+    .checked_sub(36)
+    .and_then(|start| stem.get(start..))
+    .filter(|id| id.matches('-').count() == 4);
+  Some(uuid.unwrap_or(stem).to_string())
+}
+
+pub struct CodexSource;
+
+impl UsageSource for CodexSource {
+  fn tool(&self) -> &str {
+    "Codex"
+  }
+
+  fn roots(&self, home: &str) -> Vec<String> {
+    vec![format!("{home}/.codex")]
+  }
+
+  fn file_patterns(&self) -> Vec<String> {
+    vec!["sessions/**/*.jsonl".to_string()]
+  }
+
+  fn scan_file(&self, path: &Path, state: &mut FileState) -> Vec<UsageEntry> {
+    let fallback_ts = file_mtime_rfc3339(path).unwrap_or_default();
+    let mut out = Vec::new();
+
+    // Restore last known model and running totals for this file (for incremental reads)
+    let mut current_model: Option<String> = state.model.clone();
+    let mut current_project: Option<String> = state.project.clone();
+    let mut current_service_tier = state.service_tier;
+    let mut pending_tool_calls =
+      if was_truncated(path, state) { Vec::new() } else { std::mem::take(&mut state.tool_calls) };
+    let mut prev_total = if was_truncated(path, state) { None } else { state.totals };
+    let session_id = codex_session_id(path);
+
+    for_each_new_line(path, state, |v| {
+      let ty = v.get("type").and_then(|t| t.as_str()).unwrap_or("");
+
+      if ty == "session_meta" || ty == "turn_context" {
+        if let Some(cwd) = v.pointer("/payload/cwd").and_then(|c| c.as_str()) {
+          current_project = Some(cwd.to_string());
+        }
+        // Context lines carry the full config, so a missing tier means standard.
+        current_service_tier = ServiceTier::from_value(v.pointer("/payload/service_tier"));
+      }
+
+      if ty == "turn_context" {
+        if let Some(m) = extract_codex_model(v) {
+          current_model = Some(m);
+        } else if let Some(s) = v.pointer("/payload/model").and_then(|m| m.as_str()) {
+          let s = s.trim();
+          if !s.is_empty() { current_model = Some(s.to_string()); }
+        }
+        return;
+      }
+
+      if ty == "response_item" {
+        if let Some(name) = codex_tool_call(v) {
+          pending_tool_calls.push(name);
+        }
+        return;
+      }
+
+      if ty != "event_msg" {
+        return;
+      }
+
+      let payload_type = v.pointer("/payload/type").and_then(|t| t.as_str()).unwrap_or("");
+      if payload_type != "token_count" {
+        return;
+      }
+
+      // Running totals are tracked even when a per-turn delta is logged, so a
+      // later event with only `total_token_usage` yields the right difference.
+      let cumulative = v.pointer("/payload/info/total_token_usage").map(codex_token_counters);
+      let tokens = if let Some(last) = v.pointer("/payload/info/last_token_usage") {
+        codex_token_counters(last)
+      } else if let Some(current) = cumulative {
+        current.since(prev_total.as_ref())
+      } else {
+        return;
+      };
+      if cumulative.is_some() {
+        prev_total = cumulative;
+      }
+      let TokenCounters {
+        input: input_tokens,
+        output: output_tokens,
+        cache_read: cache_read_tokens,
+        total: total_tokens,
+        ..
+      } = tokens;
+
+      if input_tokens == 0 && output_tokens == 0 && cache_read_tokens == 0 && total_tokens == 0 {
+        return;
+      }
+
+      let model = extract_codex_model(v)
+        .or_else(|| current_model.clone())
+        .unwrap_or_else(|| "gpt-5".to_string());
+
+      if let Some(m) = extract_codex_model(v) {
+        current_model = Some(m);
+      }
+
+      let ts_val = v
+        .get("timestamp")
+        .or_else(|| v.get("time"))
+        .or_else(|| v.get("created_at"))
+        .or_else(|| v.pointer("/payload/info/time"))
+        .or_else(|| v.pointer("/payload/time"));
+      let timestamp = normalize_timestamp(ts_val).unwrap_or_else(|| fallback_ts.clone());
+
+      let service_tier =
+        ServiceTier::from_value(v.pointer("/payload/info/service_tier")).or(current_service_tier);
+      let tool_calls = std::mem::take(&mut pending_tool_calls);
+      let web_search_requests = tool_calls.iter().filter(|name| *name == CODEX_WEB_SEARCH).count() as u64;
+
+      out.push(UsageEntry {
+        timestamp,
+        tool: self.tool().to_string(),
+        model,
+        input_tokens,
+        output_tokens,
+        cache_read_tokens,
+        cache_write_tokens: 0,
+        total_tokens,
+        cost: 0.0,
+        logged_cost: None,
+        provider: None,
+        service_tier,
+        session_id: session_id.clone(),
+        sidechain: false,
+        agent_id: None,
+        tool_calls,
+        web_search_requests,
+        project: current_project.clone(),
+        machine_id: None,
+      });
+    });
+
+    state.totals = prev_total;
+    if current_model.is_some() {
+      state.model = current_model;
+    }
+    if current_project.is_some() {
+      state.project = current_project;
+    }
+    state.service_tier = current_service_tier;
+    state.tool_calls = pending_tool_calls;
+
+    out
+  }
+}
+
+/// Builds an entry from one OpenCode message, as stored in a JSON file or a
+/// database row.
+fn opencode_entry(v: &Value, fallback_ts: &str) -> Option<UsageEntry> {
+  let input_tokens = value_u64(v.pointer("/tokens/input"));
+  let output_tokens = value_u64(v.pointer("/tokens/output"));
+  let cache_read_tokens = value_u64(v.pointer("/tokens/cache/read"));
+  let cache_write_tokens = value_u64(v.pointer("/tokens/cache/write"));
+  let total_tokens = {
+    let explicit_total = value_u64(
+      v.pointer("/tokens/total")
+        .or_else(|| v.pointer("/tokens/total_tokens"))
+        .or_else(|| v.get("total_tokens")),
+    );
+    if explicit_total > 0 {
+      explicit_total
+    } else {
+      total_tokens_with_cache(
+        input_tokens,
+        output_tokens,
+        cache_read_tokens,
+        cache_write_tokens,
+      )
+    }
+  };
+  let logged_cost = value_f64(v.get("cost"));
+
+  if input_tokens == 0
+    && output_tokens == 0
+    && cache_write_tokens == 0
+    && cache_read_tokens == 0
+    && logged_cost == 0.0
+  {
+    return None;
+  }
+
+  let timestamp =
+    normalize_timestamp(v.pointer("/time/created")).unwrap_or_else(|| fallback_ts.to_string());
+  let model = v
+    .get("modelID")
+    .and_then(|m| m.as_str())
+    .unwrap_or("unknown")
+    .to_string();
+
+  let service_tier = ServiceTier::from_value(
+    v.get("serviceTier")
+      .or_else(|| v.pointer("/providerMetadata/openai/serviceTier")),
+  );
+
+  Some(UsageEntry {
+    timestamp,
+    tool: "OpenCode".to_string(),
+    model,
+    input_tokens,
+    output_tokens,
+    cache_read_tokens,
+    cache_write_tokens,
+    total_tokens,
+    cost: logged_cost,
+    logged_cost: (logged_cost != 0.0).then_some(logged_cost),
+    provider: None,
+    service_tier,
+    session_id: v.get("sessionID").and_then(|s| s.as_str()).map(str::to_string),
+    sidechain: false,
+    agent_id: None,
+    tool_calls: Vec::new(),
+    web_search_requests: 0,
+    project: v
+      .pointer("/path/root")
+      .or_else(|| v.pointer("/path/cwd"))
+      .and_then(|p| p.as_str())
+      .map(str::to_string),
+    machine_id: None,
+  })
+}
+
+const OPENCODE_DB_FILE: &str = "opencode.db";
+
+/// Assistant messages still streaming are left for a later scan, unless they
+/// have not been touched for this long (e.g. an aborted request).
+const OPENCODE_PENDING_GRACE_MS: i64 = 10 * 60 * 1000;
+
+pub struct OpenCodeSource;
+
+impl OpenCodeSource {
+  /// Newer releases keep messages in a SQLite database next to `storage/`.
+  /// When it exists the legacy JSON files are migrated copies and are skipped,
+  /// and entries counted from them before are replaced by the database's.
+  fn has_database(json_path: &Path) -> bool {
+    json_path
+      .ancestors()
+      .find(|p| p.file_name().is_some_and(|n| n == "storage"))
+      .and_then(|storage| storage.parent())
+      .is_some_and(|root| root.join(OPENCODE_DB_FILE).is_file())
+  }
+
+  fn scan_json_file(&self, path: &Path, state: &mut FileState) -> Vec<UsageEntry> {
+    if Self::has_database(path) {
+      return Vec::new();
+    }
+
+    let file_len = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+
+    // For JSON files (not JSONL), skip if already processed and same size
+    if state.offset > 0 && file_len == state.offset {
+      return Vec::new();
+    }
+
+    let fallback_ts = file_mtime_rfc3339(path).unwrap_or_default();
+    let raw = match fs::read_to_string(path) {
+      Ok(s) => s,
+      Err(_) => return Vec::new(),
+    };
+
+    let v: Value = match serde_json::from_str(&raw) {
+      Ok(v) => v,
+      Err(_) => return Vec::new(),
+    };
+
+    state.offset = file_len;
+    opencode_entry(&v, &fallback_ts).into_iter().collect()
+  }
+
+  /// Reads message rows added since `state.offset`, which holds the last
+  /// processed SQLite rowid.
+  fn scan_database(&self, path: &Path, state: &mut FileState) -> Vec<UsageEntry> {
+    let conn = match Connection::open_with_flags(
+      path,
+      OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    ) {
+      Ok(c) => c,
+      Err(e) => {
+        log::warn!("Failed to open {}: {e}", path.display());
+        return Vec::new();
+      }
+    };
+    match Self::read_messages(&conn, state) {
+      Ok(entries) => entries,
+      Err(e) => {
+        log::warn!("Failed to read OpenCode messages from {}: {e}", path.display());
+        Vec::new()
+      }
+    }
+  }
+
+  /// Rows still streaming at the last scan are read again.
+  fn read_messages(conn: &Connection, state: &mut FileState) -> rusqlite::Result<Vec<UsageEntry>> {
+    let pending: Vec<String> = state.pending.iter().map(u64::to_string).collect();
+    let sql = format!(
+      "SELECT rowid, data, time_created, time_updated, session_id FROM message \
+       WHERE rowid > ?1 OR rowid IN ({}) ORDER BY rowid",
+      pending.join(",")
+    );
+    let rows = conn
+      .prepare(&sql)
+      .and_then(|mut stmt| {
+        stmt
+          .query_map([state.offset as i64], |row| {
+            Ok((
+              row.get::<_, i64>(0)?,
+              row.get::<_, String>(1)?,
+              row.get::<_, Option<i64>>(2)?,
+              row.get::<_, Option<i64>>(3)?,
+              row.get::<_, Option<String>>(4)?,
+            ))
+          })?
+          .collect::<Result<Vec<_>, _>>()
+      })?;
+
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let mut out = Vec::new();
+    state.pending.clear();
+
+    for (rowid, data, time_created, time_updated, session_id) in rows {
+      state.offset = state.offset.max(rowid as u64);
+      let Ok(v) = serde_json::from_str::<Value>(&data) else {
+        continue;
+      };
+
+      // Rows are updated in place while a response streams; unfinished ones are
+      // kept pending so each is counted once, with its final token counts.
+      let is_assistant = v.get("role").and_then(|r| r.as_str()) == Some("assistant");
+      let finished = v.pointer("/time/completed").is_some_and(|t| !t.is_null());
+      let last_touch = time_updated.or(time_created).unwrap_or(0);
+      if is_assistant && !finished && now_ms - last_touch < OPENCODE_PENDING_GRACE_MS {
+        state.pending.push(rowid as u64);
+        continue;
+      }
+
+      let fallback_ts = time_created.and_then(normalize_epoch).unwrap_or_default();
+      if let Some(mut entry) = opencode_entry(&v, &fallback_ts) {
+        // The column is always set; the copy inside `data` is not guaranteed.
+        if session_id.is_some() {
+          entry.session_id = session_id;
+        }
+        out.push(entry);
+      }
+    }
+
+    Ok(out)
+  }
+}
+
+impl UsageSource for OpenCodeSource {
+  fn tool(&self) -> &str {
+    "OpenCode"
+  }
+
+  fn roots(&self, home: &str) -> Vec<String> {
+    vec![format!("{home}/.local/share/opencode")]
+  }
+
+  fn file_patterns(&self) -> Vec<String> {
+    vec![
+      OPENCODE_DB_FILE.to_string(),
+      "storage/message/**/*.json".to_string(),
+    ]
+  }
+
+  fn scan_file(&self, path: &Path, state: &mut FileState) -> Vec<UsageEntry> {
+    if path.file_name().is_some_and(|n| n == OPENCODE_DB_FILE) {
+      self.scan_database(path, state)
+    } else {
+      self.scan_json_file(path, state)
+    }
+  }
+
+  fn replaces_other_files(&self, path: &Path) -> bool {
+    path.file_name().is_some_and(|n| n == OPENCODE_DB_FILE)
+  }
+}
+
+pub struct GeminiSource;
+
+impl UsageSource for GeminiSource {
+  fn tool(&self) -> &str {
+    "Gemini"
+  }
+
+  fn roots(&self, home: &str) -> Vec<String> {
+    vec![format!("{home}/.gemini")]
+  }
+
+  fn file_patterns(&self) -> Vec<String> {
+    vec!["tmp/*/chats/*.json".to_string()]
+  }
+
+  fn scan_file(&self, path: &Path, state: &mut FileState) -> Vec<UsageEntry> {
+    let fallback_ts = file_mtime_rfc3339(path).unwrap_or_default();
+    let raw = match fs::read_to_string(path) {
+      Ok(s) => s,
+      Err(_) => return Vec::new(),
+    };
+
+    let v: Value = match serde_json::from_str(&raw) {
+      Ok(v) => v,
+      Err(_) => return Vec::new(),
+    };
+
+    let Some(messages) = v.get("messages").and_then(|m| m.as_array()) else {
+      return Vec::new();
+    };
+
+    // Session files are rewritten in place as the chat grows, so track how many
+    // messages were already counted instead of a byte offset. Reset if the file shrank.
+    // A reply can be written before its token counts, so the count only moves past
+    // the last message counted and later ones are looked at again on the next scan.
+    let prev_count = state.offset as usize;
+    let start = if prev_count > messages.len() { 0 } else { prev_count };
+    state.offset = start as u64;
+
+    let session_id = v.get("sessionId").and_then(|s| s.as_str()).map(str::to_string);
+    let mut out = Vec::new();
+    for (i, msg) in messages.iter().enumerate().skip(start) {
+      let Some(tokens) = msg.get("tokens") else {
+        continue;
+      };
+
+      // Gemini reports prompt tokens inclusive of cached content; thoughts are billed as output.
+      let prompt_tokens = value_u64(tokens.get("input"));
+      let tool_tokens = value_u64(tokens.get("tool"));
+      let input_tokens = prompt_tokens.saturating_add(tool_tokens);
+      let output_tokens = value_u64(tokens.get("output")).saturating_add(value_u64(tokens.get("thoughts")));
+      let cache_read_tokens = value_u64(tokens.get("cached"));
+      let total_tokens = {
+        let explicit_total = value_u64(tokens.get("total"));
+        if explicit_total > 0 {
+          explicit_total
+        } else {
+          total_tokens_without_cache(input_tokens, output_tokens)
+        }
+      };
+
+      if input_tokens == 0 && output_tokens == 0 && cache_read_tokens == 0 && total_tokens == 0 {
+        continue;
+      }
+      state.offset = i as u64 + 1;
+
+      let timestamp = normalize_timestamp(msg.get("timestamp"))
+        .or_else(|| normalize_timestamp(v.get("lastUpdated")))
+        .unwrap_or_else(|| fallback_ts.clone());
+      let model = msg
+        .get("model")
+        .and_then(|m| m.as_str())
+        .map(|m| m.trim())
+        .filter(|m| !m.is_empty())
+        .unwrap_or("unknown")
+        .to_string();
+
+      out.push(UsageEntry {
+        timestamp,
+        tool: self.tool().to_string(),
+        model,
+        input_tokens,
+        output_tokens,
+        cache_read_tokens,
+        cache_write_tokens: 0,
+        total_tokens,
+        cost: 0.0,
+        logged_cost: None,
+        provider: None,
+        service_tier: None,
+        session_id: session_id.clone(),
+        sidechain: false,
+        agent_id: None,
+        tool_calls: Vec::new(),
+        web_search_requests: 0,
+        project: None,
+        machine_id: None,
+      });
+    }
+
+    out
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Write;
+  use std::path::PathBuf;
+
+  fn temp_log(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("token-viewer-{}-{name}.jsonl", std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+  }
+
+  fn append(path: &Path, lines: &[&str]) {
+    let mut f = fs::OpenOptions::new().create(true).append(true).open(path).unwrap();
+    for line in lines {
+      writeln!(f, "{line}").unwrap();
+    }
+  }
+
+  fn total_event(input: u64, cached: u64, output: u64) -> String {
+    format!(
+      r#"{{"type":"event_msg","timestamp":"2025-01-01T00:00:00Z","payload":{{"type":"token_count","info":{{"total_token_usage":{{"input_tokens":{input},"cached_input_tokens":{cached},"output_tokens":{output}}}}}}}}}"#
+    )
+  }
+
+  fn sums(entries: &[UsageEntry]) -> (u64, u64, u64) {
+    entries.iter().fold((0, 0, 0), |(i, c, o), e| {
+      (i + e.input_tokens, c + e.cache_read_tokens, o + e.output_tokens)
+    })
+  }
+
+  #[test]
+  fn codex_resumed_scan_counts_only_new_cumulative_delta() {
+    let path = temp_log("codex-resume");
+    append(&path, &[&total_event(100, 20, 10), &total_event(250, 50, 30)]);
+
+    let mut state = FileState::default();
+    let first = CodexSource.scan_file(&path, &mut state);
+    assert_eq!(sums(&first), (250, 50, 30));
+
+    append(&path, &[&total_event(400, 80, 45)]);
+    let second = CodexSource.scan_file(&path, &mut state);
+    assert_eq!(second.len(), 1);
+    assert_eq!(sums(&second), (150, 30, 15));
+
+    let _ = fs::remove_file(&path);
+  }
+
+  #[test]
+  fn codex_resumed_scan_matches_full_scan() {
+    let path = temp_log("codex-full");
+    append(&path, &[&total_event(10, 0, 5)]);
+
+    let mut state = FileState::default();
+    let mut incremental = CodexSource.scan_file(&path, &mut state);
+    append(&path, &[&total_event(30, 10, 8), &total_event(70, 30, 20)]);
+    incremental.extend(CodexSource.scan_file(&path, &mut state));
+
+    let full = CodexSource.scan_file(&path, &mut FileState::default());
+    assert_eq!(sums(&incremental), sums(&full));
+    assert_eq!(sums(&full), (70, 30, 20));
+
+    let _ = fs::remove_file(&path);
+  }
+
+  #[test]
+  fn codex_truncated_file_resets_running_totals() {
+    let path = temp_log("codex-truncate");
+    append(&path, &[&total_event(500, 0, 50), &total_event(900, 0, 90)]);
+
+    let mut state = FileState::default();
+    CodexSource.scan_file(&path, &mut state);
+
+    fs::remove_file(&path).unwrap();
+    append(&path, &[&total_event(40, 0, 4)]);
+    let rescanned = CodexSource.scan_file(&path, &mut state);
+    assert_eq!(sums(&rescanned), (40, 0, 4));
+
+    let _ = fs::remove_file(&path);
+  }
+
+  #[test]
+  fn gemini_counts_cached_thoughts_and_tool_tokens() {
+    let path = temp_log("gemini").with_extension("json");
+    let chat = |messages: &str| {
+      format!(r#"{{"sessionId":"g1","lastUpdated":"2025-01-01T00:05:00Z","messages":[{messages}]}}"#)
+    };
+    let first = r#"{"type":"user","content":"hi"},{"type":"gemini","timestamp":"2025-01-01T00:00:00Z","model":"gemini-2.5-pro","tokens":{"input":1000,"output":50,"cached":400,"thoughts":30,"tool":20,"total":1100}}"#;
+    fs::write(&path, chat(first)).unwrap();
+
+    let mut state = FileState::default();
+    let entries = GeminiSource.scan_file(&path, &mut state);
+    assert_eq!(entries.len(), 1);
+    let e = &entries[0];
+    // Tool prompt tokens are input; thoughts are billed as output.
+    assert_eq!((e.input_tokens, e.output_tokens, e.cache_read_tokens), (1020, 80, 400));
+    assert_eq!(e.total_tokens, 1100);
+    assert_eq!(e.session_id.as_deref(), Some("g1"));
+
+    // The file is rewritten as the chat grows; only new messages are counted.
+    let second = r#"{"type":"gemini","timestamp":"2025-01-01T00:01:00Z","model":"gemini-2.5-pro","tokens":{"input":10,"output":5}}"#;
+    fs::write(&path, chat(&format!("{first},{second}"))).unwrap();
+    let entries = GeminiSource.scan_file(&path, &mut state);
+    assert_eq!(entries.len(), 1);
+    assert_eq!((entries[0].input_tokens, entries[0].total_tokens), (10, 15));
+
+    let _ = fs::remove_file(&path);
+  }
+
+  #[test]
+  fn gemini_counts_replies_once_their_tokens_are_written() {
+    let path = temp_log("gemini-pending").with_extension("json");
+    let chat = |reply: &str| {
+      format!(r#"{{"sessionId":"g2","messages":[{{"type":"user","content":"hi"}},{{"type":"gemini","timestamp":"2025-01-01T00:00:00Z","model":"gemini-2.5-pro"{reply}}}]}}"#)
+    };
+    fs::write(&path, chat("")).unwrap();
+    let mut state = FileState::default();
+    assert!(GeminiSource.scan_file(&path, &mut state).is_empty());
+
+    // The same reply rewritten in place with its token counts filled in.
+    fs::write(&path, chat(r#","tokens":{"input":10,"output":5}"#)).unwrap();
+    let entries = GeminiSource.scan_file(&path, &mut state);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].output_tokens, 5);
+    assert!(GeminiSource.scan_file(&path, &mut state).is_empty());
+
+    let _ = fs::remove_file(&path);
+  }
+
+  #[test]
+  fn opencode_database_rows_resume_and_wait_for_streaming_messages() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn
+      .execute_batch(
+        "CREATE TABLE message (id TEXT PRIMARY KEY, session_id TEXT NOT NULL, time_created INTEGER, time_updated INTEGER, data TEXT NOT NULL);",
+      )
+      .unwrap();
+    let now = chrono::Utc::now().timestamp_millis();
+    let insert = |id: &str, session: &str, data: &str| {
+      conn
+        .execute(
+          "INSERT INTO message (id, session_id, time_created, time_updated, data) VALUES (?1, ?2, ?3, ?3, ?4)",
+          rusqlite::params![id, session, now, data],
+        )
+        .unwrap();
+    };
+    let done = r#"{"role":"assistant","modelID":"claude-sonnet-4","time":{"created":1735689600000,"completed":1735689601000},"tokens":{"input":10,"output":5,"cache":{"read":3,"write":2}}}"#;
+    insert("m1", "ses_a", r#"{"role":"user","time":{"created":1735689600000}}"#);
+    insert("m2", "ses_a", done);
+    insert("m3", "ses_b", r#"{"role":"assistant","modelID":"claude-sonnet-4","time":{"created":1735689600000},"tokens":{"input":1,"output":1}}"#);
+    insert("m4", "ses_c", done);
+
+    let mut state = FileState::default();
+    let entries = OpenCodeSource::read_messages(&conn, &mut state).unwrap();
+    // The streaming message is left for the next scan without holding back later rows.
+    let sessions: Vec<_> = entries.iter().map(|e| e.session_id.as_deref()).collect();
+    assert_eq!(sessions, [Some("ses_a"), Some("ses_c")]);
+    assert_eq!((entries[0].cache_read_tokens, entries[0].cache_write_tokens), (3, 2));
+    assert_eq!((state.offset, state.pending.as_slice()), (4, [3].as_slice()));
+    assert!(OpenCodeSource::read_messages(&conn, &mut state).unwrap().is_empty());
+
+    conn.execute("UPDATE message SET data = ?1 WHERE id = 'm3'", [done]).unwrap();
+    let entries = OpenCodeSource::read_messages(&conn, &mut state).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].session_id.as_deref(), Some("ses_b"));
+    assert!(state.pending.is_empty());
+    assert!(OpenCodeSource::read_messages(&conn, &mut state).unwrap().is_empty());
+  }
+
+  #[test]
+  fn claude_subagent_requests_are_marked_as_sidechain() {
+    let line = |sidechain: bool| {
+      format!(
+        r#"{{"type":"assistant","timestamp":"2025-01-01T00:00:00Z","sessionId":"s1","isSidechain":{sidechain},"message":{{"model":"claude-sonnet-4","usage":{{"input_tokens":10,"output_tokens":5}}}}}}"#
+      )
+    };
+    let main = temp_log("claude-main");
+    append(&main, &[&line(false), &line(true)]);
+    let entries = ClaudeSource.scan_file(&main, &mut FileState::default());
+    assert!(!entries[0].sidechain && entries[0].agent_id.is_none());
+    assert!(entries[1].sidechain);
+
+    let dir = std::env::temp_dir().join(format!("token-viewer-{}-s1", std::process::id())).join("subagents");
+    fs::create_dir_all(&dir).unwrap();
+    let agent = dir.join("agent-a1b2.jsonl");
+    let _ = fs::remove_file(&agent);
+    append(&agent, &[&line(false)]);
+    let entries = ClaudeSource.scan_file(&agent, &mut FileState::default());
+    assert!(entries[0].sidechain);
+    assert_eq!(entries[0].agent_id.as_deref(), Some("a1b2"));
+    assert_eq!(entries[0].session_id.as_deref(), Some("s1"));
+
+    let _ = fs::remove_file(&main);
+    let _ = fs::remove_dir_all(dir.parent().unwrap());
+  }
+
+  #[test]
+  fn claude_web_searches_count_once_per_message() {
+    let line = |id: &str| {
+      format!(
+        r#"{{"type":"assistant","timestamp":"2025-01-01T00:00:00Z","message":{{"id":"{id}","model":"claude-sonnet-4","usage":{{"input_tokens":10,"output_tokens":5,"server_tool_use":{{"web_search_requests":2}}}}}}}}"#
+      )
+    };
+    let log = temp_log("claude-searches");
+    append(&log, &[&line("msg_1"), &line("msg_1")]);
+    let mut state = FileState::default();
+    let searches = |entries: Vec<UsageEntry>| entries.iter().map(|e| e.web_search_requests).sum::<u64>();
+    assert_eq!(searches(ClaudeSource.scan_file(&log, &mut state)), 2);
+
+    // The next block of the same message may only be written after a scan.
+    append(&log, &[&line("msg_1"), &line("msg_2")]);
+    assert_eq!(searches(ClaudeSource.scan_file(&log, &mut state)), 2);
+
+    let _ = fs::remove_file(&log);
+  }
+
+  #[test]
+  fn tool_calls_attach_to_the_request_that_made_them() {
+    let claude = temp_log("claude-tools");
+    append(&claude, &[
+      r#"{"timestamp":"2025-01-01T00:00:00Z","message":{"model":"claude-sonnet-4","usage":{"input_tokens":10,"output_tokens":5,"server_tool_use":{"web_search_requests":2}},"content":[{"type":"text","text":"ok"},{"type":"tool_use","name":"Bash"},{"type":"server_tool_use","name":"web_search"}]}}"#,
+    ]);
+    let entries = ClaudeSource.scan_file(&claude, &mut FileState::default());
+    assert_eq!(entries[0].tool_calls, ["Bash", "web_search"]);
+    assert_eq!(entries[0].web_search_requests, 2);
+
+    // A call logged before a scan is attributed to the token count after it.
+    let codex = temp_log("codex-tools");
+    append(&codex, &[
+      r#"{"type":"response_item","payload":{"type":"function_call","name":"shell"}}"#,
+      r#"{"type":"response_item","payload":{"type":"web_search_call"}}"#,
+    ]);
+    let mut state = FileState::default();
+    assert!(CodexSource.scan_file(&codex, &mut state).is_empty());
+    append(&codex, &[&total_event(100, 0, 10), &total_event(200, 0, 20)]);
+    let entries = CodexSource.scan_file(&codex, &mut state);
+    assert_eq!(entries[0].tool_calls, ["shell", "web_search"]);
+    assert_eq!(entries[0].web_search_requests, 1);
+    assert!(entries[1].tool_calls.is_empty());
+
+    let _ = fs::remove_file(&claude);
+    let _ = fs::remove_file(&codex);
+  }
+
+  #[test]
+  fn codex_service_tier_carries_across_resumed_scans() {
+    let path = temp_log("codex-tier");
+    append(&path, &[
+      r#"{"type":"turn_context","payload":{"model":"gpt-5","service_tier":"flex"}}"#,
+      &total_event(100, 0, 10),
+    ]);
+
+    let mut state = FileState::default();
+    let first = CodexSource.scan_file(&path, &mut state);
+    assert_eq!(first[0].service_tier, Some(ServiceTier::Flex));
+
+    append(&path, &[&total_event(200, 0, 20)]);
+    let second = CodexSource.scan_file(&path, &mut state);
+    assert_eq!(second[0].service_tier, Some(ServiceTier::Flex));
+
+    append(&path, &[r#"{"type":"turn_context","payload":{"model":"gpt-5"}}"#, &total_event(300, 0, 30)]);
+    let third = CodexSource.scan_file(&path, &mut state);
+    assert_eq!(third[0].service_tier, None);
+
+    let _ = fs::remove_file(&path);
+  }
+}
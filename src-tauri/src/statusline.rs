@@ -0,0 +1,419 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, ExitCode, Stdio};
+use std::time::Duration;
+
+use crate::aggregate::usage_blocks;
+use crate::cost_mode::{self, CostMode};
+use crate::currency::Converter;
+use crate::providers::{load_rules, provider_for};
+use crate::sources::{ClaudeSource, FileState, UsageSource};
+use crate::{app_cache_dir, write_atomic, PricingTable, ScanState, UsageEntry};
+
+const SUMMARY_FILE: &str = "statusline_summary.json";
+const SUMMARY_VERSION: u32 = 1;
+
+/// The status line only reads the summary. Once it is this old, a prompt
+/// starts a background rescan that rewrites it.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Touched when a background rescan starts, so prompts in quick succession start one.
+const REFRESH_MARKER_FILE: &str = "statusline_refresh";
+
+/// Cost of one session's requests on one local date, in USD.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SessionDayCost {
+  session_id: String,
+  date: NaiveDate,
+  cost: f64,
+}
+
+/// The part of the scan index the status line needs, written next to it so a
+/// prompt reads the last day of usage instead of the whole history.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Summary {
+  version: u32,
+  cost_mode: CostMode,
+  /// Local entries since the start of the day the summary was written or the
+  /// start of the latest block, whichever is earlier.
+  entries: Vec<UsageEntry>,
+  /// Start of the latest block. Blocks chain, so they are rebuilt from here.
+  block_start: Option<DateTime<Utc>>,
+  /// Costs of the Claude sessions in `entries` from before them.
+  earlier_session_costs: Vec<SessionDayCost>,
+  /// Scan positions of those sessions' transcripts, by session id.
+  transcripts: HashMap<String, FileState>,
+  /// Entries read from the transcript that the cached price table could not price.
+  #[serde(skip)]
+  unpriced: Vec<UsageEntry>,
+}
+
+fn start_of_day(now: DateTime<Utc>) -> DateTime<Utc> {
+  let midnight = now.with_timezone(&Local).date_naive().and_time(NaiveTime::MIN);
+  Local
+    .from_local_datetime(&midnight)
+    .earliest()
+    .map_or(now, |t| t.with_timezone(&Utc))
+}
+
+impl Summary {
+  fn build(state: &ScanState, now: DateTime<Utc>) -> Summary {
+    let local: Vec<UsageEntry> =
+      state.cached_entries.iter().filter(|e| e.machine_id.is_none()).cloned().collect();
+    let block_start = usage_blocks(&local, now)
+      .last()
+      .and_then(|b| DateTime::parse_from_rfc3339(&b.start).ok())
+      .map(|t| t.with_timezone(&Utc));
+    let day_start = start_of_day(now);
+    let window_start = block_start.map_or(day_start, |start| start.min(day_start));
+    let (entries, earlier): (Vec<UsageEntry>, Vec<UsageEntry>) =
+      local.into_iter().partition(|e| e.utc_time().is_some_and(|t| t >= window_start));
+
+    let claude = ClaudeSource.tool();
+    let sessions: HashSet<&str> = entries
+      .iter()
+      .filter(|e| e.tool == claude)
+      .filter_map(|e| e.session_id.as_deref())
+      .collect();
+    let mut earlier_costs: BTreeMap<(&str, NaiveDate), f64> = BTreeMap::new();
+    for e in earlier.iter().filter(|e| e.tool == claude) {
+      let (Some(session), Some(t)) = (e.session_id.as_deref(), e.local_time()) else {
+        continue;
+      };
+      if sessions.contains(session) {
+        *earlier_costs.entry((session, t.date_naive())).or_default() += e.cost;
+      }
+    }
+    let earlier_session_costs = earlier_costs
+      .into_iter()
+      .map(|((session_id, date), cost)| SessionDayCost { session_id: session_id.to_string(), date, cost })
+      .collect();
+    // Claude Code names each transcript after its session.
+    let transcripts = state
+      .file_states
+      .iter()
+      .filter_map(|(path, file_state)| {
+        let stem = Path::new(path).file_stem()?.to_str()?;
+        sessions.contains(stem).then(|| (stem.to_string(), file_state.clone()))
+      })
+      .collect();
+
+    Summary {
+      version: SUMMARY_VERSION,
+      cost_mode: state.cost_mode,
+      entries,
+      block_start,
+      earlier_session_costs,
+      transcripts,
+      unpriced: Vec::new(),
+    }
+  }
+
+  /// Adds what the session's transcript logged since the summary was written.
+  /// New entries are priced from the cached price table only, never fetching.
+  fn add_transcript(&mut self, session: &Value) {
+    let Some(path) = session.get("transcript_path").and_then(Value::as_str).map(Path::new) else {
+      return;
+    };
+    let Some(session_id) = path.file_stem().and_then(|s| s.to_str()) else {
+      return;
+    };
+    let mut state = match self.transcripts.get(session_id) {
+      Some(state) => state.clone(),
+      // Without its position, lines already in the summary would be counted again.
+      None if self.entries.iter().any(|e| e.session_id.as_deref() == Some(session_id)) => return,
+      None => FileState::default(),
+    };
+    let mut entries = ClaudeSource.scan_file(path, &mut state);
+    if entries.is_empty() {
+      return;
+    }
+    if let Some(provider) = provider_for(&load_rules(), ClaudeSource.tool(), path) {
+      for e in &mut entries {
+        e.provider = Some(provider);
+      }
+    }
+
+    let mode = self.cost_mode;
+    let needs_price = |e: &UsageEntry| match mode {
+      CostMode::Auto => e.logged_cost.is_none(),
+      CostMode::Calculate => true,
+      CostMode::Display => false,
+    };
+    let pricing = if entries.iter().any(needs_price) {
+      PricingTable::cached()
+    } else {
+      PricingTable::new(HashMap::new())
+    };
+    mode.apply(&mut entries, &pricing);
+    let unpriced = entries.iter().filter(|e| needs_price(e) && pricing.find(&e.model, e.provider).is_none());
+    self.unpriced.extend(unpriced.cloned());
+    self.entries.extend(entries);
+  }
+}
+
+/// Rewrites the summary from `state`. Called whenever the scan index is saved.
+pub fn save_summary(state: &ScanState) {
+  let Some(path) = app_cache_dir().map(|d| d.join(SUMMARY_FILE)) else {
+    return;
+  };
+  let result = serde_json::to_vec(&Summary::build(state, Utc::now()))
+    .map_err(|e| e.to_string())
+    .and_then(|json| write_atomic(&path, &json));
+  if let Err(e) = result {
+    log::warn!("Failed to save status line summary: {e}");
+  }
+}
+
+/// The saved summary and its age, if it exists and is current.
+fn load_summary() -> Option<(Summary, Duration)> {
+  let path = app_cache_dir()?.join(SUMMARY_FILE);
+  let raw = fs::read(&path).ok()?;
+  let age = fs::metadata(&path).ok()?.modified().ok()?.elapsed().unwrap_or_default();
+  match serde_json::from_slice::<Summary>(&raw) {
+    Ok(summary) if summary.version == SUMMARY_VERSION => Some((summary, age)),
+    Ok(_) => None,
+    Err(e) => {
+      log::warn!("Ignoring unreadable status line summary {}: {e}", path.display());
+      None
+    }
+  }
+}
+
+/// Starts `statusline --refresh` in the background, unless one started within
+/// `REFRESH_INTERVAL`.
+fn spawn_refresh() {
+  let Some(marker) = app_cache_dir().map(|d| d.join(REFRESH_MARKER_FILE)) else {
+    return;
+  };
+  let started = fs::metadata(&marker).ok().and_then(|m| m.modified().ok()?.elapsed().ok());
+  if started.is_some_and(|age| age < REFRESH_INTERVAL) {
+    return;
+  }
+  if let Err(e) = write_atomic(&marker, b"") {
+    log::warn!("Failed to mark status line refresh: {e}");
+    return;
+  }
+  let spawned = std::env::current_exe().and_then(|exe| {
+    Command::new(exe)
+      .args(["statusline", "--refresh"])
+      .stdin(Stdio::null())
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .spawn()
+  });
+  if let Err(e) = spawned {
+    log::warn!("Failed to start status line refresh: {e}");
+  }
+}
+
+/// Picks up new log lines and rewrites the index and the summary.
+fn refresh() {
+  let mut state = ScanState::load_index();
+  if state.scan_incremental() > 0 {
+    state.save_index();
+  } else {
+    // Still rewritten: the day or the latest block may have ended.
+    save_summary(&state);
+  }
+}
+
+fn format_duration(minutes: i64) -> String {
+  let minutes = minutes.max(0);
+  if minutes >= 60 {
+    format!("{}h {}m", minutes / 60, minutes % 60)
+  } else {
+    format!("{minutes}m")
+  }
+}
+
+/// Builds the status line from the Claude Code session JSON and the summary.
+/// Amounts including requests that could not be priced end in `+?`.
+fn statusline_text(session: &Value, summary: &Summary, now: DateTime<Utc>, converter: &Converter) -> String {
+  let entries = &*converter.entries(&summary.entries);
+  let amount = |cost: f64, unknown: bool| {
+    if unknown {
+      format!("{}+?", converter.format(cost))
+    } else {
+      converter.format(cost)
+    }
+  };
+  let unknown = |counts: &dyn Fn(&UsageEntry) -> bool| summary.unpriced.iter().any(counts);
+
+  let today = now.with_timezone(&Local).date_naive();
+  let is_today = |e: &UsageEntry| e.local_time().is_some_and(|t| t.date_naive() == today);
+  let today_cost = entries.iter().filter(|e| is_today(e)).fold(0.0, |sum, e| sum + e.cost);
+
+  let session_id = session.get("session_id").and_then(Value::as_str);
+  let in_session = |e: &UsageEntry| session_id.is_some() && e.session_id.as_deref() == session_id;
+  let earlier_session_cost = summary
+    .earlier_session_costs
+    .iter()
+    .filter(|c| Some(c.session_id.as_str()) == session_id)
+    .fold(0.0, |sum, c| sum + converter.convert(c.cost, Some(c.date)));
+  let session_cost = entries.iter().filter(|e| in_session(e)).fold(earlier_session_cost, |sum, e| sum + e.cost);
+
+  let mut parts = Vec::new();
+  if let Some(model) = session.pointer("/model/display_name").and_then(Value::as_str) {
+    parts.push(model.to_string());
+  }
+  parts.push(format!("{} today", amount(today_cost, unknown(&is_today))));
+  parts.push(format!("{} session", amount(session_cost, unknown(&in_session))));
+
+  let since_block_start = |e: &UsageEntry| match summary.block_start {
+    Some(start) => e.utc_time().is_some_and(|t| t >= start),
+    None => true,
+  };
+  let block_entries: Vec<UsageEntry> = entries.iter().filter(|e| since_block_start(e)).cloned().collect();
+  let active = usage_blocks(&block_entries, now).into_iter().rev().find(|b| b.is_active);
+  match active {
+    Some(block) => {
+      let start = DateTime::parse_from_rfc3339(&block.start).map(|t| t.with_timezone(&Utc)).ok();
+      let in_block = |e: &UsageEntry| e.utc_time().is_some_and(|t| start.is_some_and(|start| t >= start));
+      let unknown = unknown(&in_block);
+      let left = DateTime::parse_from_rfc3339(&block.end)
+        .map(|end| (end.with_timezone(&Utc) - now).num_minutes())
+        .unwrap_or_default();
+      parts.push(format!(
+        "{}/h block ({}, {} left)",
+        amount(block.cost_per_hour, unknown),
+        amount(block.totals.cost, unknown),
+        format_duration(left)
+      ));
+    }
+    None => parts.push("no active block".to_string()),
+  }
+  parts.join(" | ")
+}
+
+/// Entry point of `token-viewer-cli statusline`. Reads the summary saved with
+/// the scan index plus the current transcript's new lines, never the whole
+/// index, so it stays fast on every prompt.
+pub fn run(args: &[String]) -> ExitCode {
+  if args.iter().any(|a| a == "--refresh") {
+    refresh();
+    return ExitCode::SUCCESS;
+  }
+  let mut input = String::new();
+  if let Err(e) = std::io::stdin().read_to_string(&mut input) {
+    eprintln!("failed to read stdin: {e}");
+    return ExitCode::FAILURE;
+  }
+  // Claude Code always sends JSON, but keep working with an empty or odd payload.
+  let session: Value = serde_json::from_str(&input).unwrap_or(Value::Null);
+  let mut summary = match load_summary() {
+    Some((summary, age)) => {
+      if age >= REFRESH_INTERVAL {
+        spawn_refresh();
+      }
+      summary
+    }
+    None => {
+      spawn_refresh();
+      Summary { version: SUMMARY_VERSION, cost_mode: cost_mode::load_mode(), ..Default::default() }
+    }
+  };
+  summary.add_transcript(&session);
+  println!("{}", statusline_text(&session, &summary, Utc::now(), &Converter::load()));
+  ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::{Duration as ChronoDuration, Timelike};
+  use serde_json::json;
+
+  fn entry(at: DateTime<Utc>, session: &str, cost: f64) -> UsageEntry {
+    UsageEntry {
+      timestamp: at.to_rfc3339(),
+      tool: "Claude".to_string(),
+      model: "claude-sonnet-4".to_string(),
+      input_tokens: 100,
+      total_tokens: 100,
+      cost,
+      session_id: Some(session.to_string()),
+      ..Default::default()
+    }
+  }
+
+  /// Late morning local time on a whole UTC hour, since blocks start on UTC
+  /// hours, so the fixtures below stay on one local day in every time zone.
+  fn morning() -> DateTime<Utc> {
+    let local = Local.with_ymd_and_hms(2025, 3, 10, 10, 0, 0).earliest().unwrap();
+    local.with_timezone(&Utc).with_minute(0).unwrap()
+  }
+
+  fn summary(entries: Vec<UsageEntry>, now: DateTime<Utc>) -> Summary {
+    let state = ScanState { file_states: HashMap::new(), cached_entries: entries, cost_mode: CostMode::Auto };
+    Summary::build(&state, now)
+  }
+
+  #[test]
+  fn shows_today_session_and_block_burn_rate() {
+    let start = morning();
+    let now = start + ChronoDuration::hours(2);
+    let s = summary(
+      vec![
+        entry(start - ChronoDuration::days(1), "s1", 1.0),
+        entry(start, "s1", 2.0),
+        entry(start + ChronoDuration::hours(1), "s2", 3.0),
+      ],
+      now,
+    );
+    // Yesterday's request only counts toward its session.
+    assert_eq!(s.entries.len(), 2);
+    assert_eq!(s.earlier_session_costs.len(), 1);
+
+    let session = json!({ "session_id": "s1", "model": { "display_name": "Sonnet 4" } });
+    assert_eq!(
+      statusline_text(&session, &s, now, &Converter::usd()),
+      "Sonnet 4 | $5.00 today | $3.00 session | $5.00/h block ($5.00, 3h 0m left)"
+    );
+  }
+
+  #[test]
+  fn reports_no_active_block_after_five_idle_hours() {
+    let start = morning();
+    let now = start + ChronoDuration::hours(6);
+    let s = summary(vec![entry(start, "s1", 2.0)], now);
+    assert_eq!(
+      statusline_text(&Value::Null, &s, now, &Converter::usd()),
+      "$2.00 today | $0.00 session | no active block"
+    );
+  }
+
+  #[test]
+  fn marks_amounts_with_unpriced_requests_as_unknown() {
+    let start = morning();
+    let now = start + ChronoDuration::minutes(30);
+    let mut s = summary(vec![entry(start, "s1", 2.0)], now);
+    let unpriced = entry(start + ChronoDuration::minutes(20), "s1", 0.0);
+    s.entries.push(unpriced.clone());
+    s.unpriced.push(unpriced);
+
+    let session = json!({ "session_id": "s1" });
+    assert_eq!(
+      statusline_text(&session, &s, now, &Converter::usd()),
+      "$2.00+? today | $2.00+? session | $6.00+?/h block ($2.00+?, 4h 30m left)"
+    );
+    let other = json!({ "session_id": "s2" });
+    assert!(statusline_text(&other, &s, now, &Converter::usd()).contains("$0.00 session"));
+  }
+
+  #[test]
+  fn keeps_the_whole_latest_block_when_it_began_yesterday() {
+    let midnight = start_of_day(morning());
+    // A block running over midnight, on a whole UTC hour.
+    let start = (midnight - ChronoDuration::hours(2)).with_minute(0).unwrap();
+    let now = start + ChronoDuration::hours(3);
+    let s = summary(vec![entry(start, "s1", 1.0), entry(start + ChronoDuration::hours(2), "s1", 2.0)], now);
+    assert_eq!(s.entries.len(), 2);
+    assert_eq!(s.block_start, Some(start));
+    assert!(statusline_text(&Value::Null, &s, now, &Converter::usd()).ends_with("($3.00, 2h 0m left)"));
+  }
+}
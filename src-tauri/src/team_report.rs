@@ -0,0 +1,291 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::aggregate::{AggregateRow, EntryFilter, Totals};
+use crate::bundle::{new_entries, read_bundle};
+use crate::currency::Converter;
+use crate::UsageEntry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+  Markdown,
+  Csv,
+  Json,
+}
+
+impl ReportFormat {
+  pub fn parse(s: &str) -> Option<Self> {
+    match s.to_lowercase().as_str() {
+      "markdown" | "md" => Some(ReportFormat::Markdown),
+      "csv" => Some(ReportFormat::Csv),
+      "json" => Some(ReportFormat::Json),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TeamReport {
+  pub since: Option<NaiveDate>,
+  pub until: Option<NaiveDate>,
+  /// Currency of every cost in the report.
+  pub currency: String,
+  pub bundles: usize,
+  pub machines: usize,
+  /// Files in the directory that could not be read as bundles, with the reason.
+  pub skipped: Vec<String>,
+  pub total: Totals,
+  pub users: Vec<AggregateRow>,
+  pub models: Vec<AggregateRow>,
+  pub projects: Vec<AggregateRow>,
+}
+
+/// Projects are keyed by the last path component, since each user checks the
+/// same repository out under a different home directory.
+fn project_name(e: &UsageEntry) -> String {
+  e.project
+    .as_deref()
+    .and_then(|p| p.trim_end_matches(['/', '\\']).rsplit(['/', '\\']).next())
+    .filter(|p| !p.is_empty())
+    .unwrap_or("unknown")
+    .to_string()
+}
+
+/// Rows sorted by cost, highest first.
+fn rows(groups: BTreeMap<String, Totals>) -> Vec<AggregateRow> {
+  let mut rows: Vec<AggregateRow> = groups
+    .into_iter()
+    .map(|(key, totals)| AggregateRow { key, totals })
+    .collect();
+  rows.sort_by(|a, b| b.totals.cost.total_cmp(&a.totals.cost));
+  rows
+}
+
+/// Reads every `.json`/`.jsonl` bundle in `dir` and totals the entries in
+/// `[since, until]` per user, model and project. Bundles from the same machine
+/// may overlap (e.g. monthly re-exports) and are de-duplicated. Costs are
+/// converted to the converter's currency.
+pub fn build(
+  dir: &Path,
+  since: Option<NaiveDate>,
+  until: Option<NaiveDate>,
+  converter: &Converter,
+) -> Result<TeamReport, String> {
+  let mut paths: Vec<_> = fs::read_dir(dir)
+    .map_err(|e| format!("{}: {e}", dir.display()))?
+    .filter_map(|entry| Some(entry.ok()?.path()))
+    .filter(|p| {
+      p.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json") || ext.eq_ignore_ascii_case("jsonl"))
+    })
+    .collect();
+  paths.sort();
+
+  // machine id -> (user, entries)
+  let mut machines: HashMap<String, (String, Vec<UsageEntry>)> = HashMap::new();
+  let mut bundles = 0;
+  let mut skipped = Vec::new();
+  for path in &paths {
+    let bundle = match read_bundle(path) {
+      Ok(b) => b,
+      Err(e) => {
+        skipped.push(e);
+        continue;
+      }
+    };
+    bundles += 1;
+    let user = bundle.header.user().to_string();
+    let machine = machines
+      .entry(bundle.header.machine_id.clone())
+      .or_insert_with(|| (user.clone(), Vec::new()));
+    machine.0 = user;
+    let (added, _) = new_entries(&machine.1, bundle.entries);
+    machine.1.extend(added);
+  }
+
+  let filter = EntryFilter { since, until, ..Default::default() };
+  let mut total = Totals::default();
+  let mut users: BTreeMap<String, Totals> = BTreeMap::new();
+  let mut models: BTreeMap<String, Totals> = BTreeMap::new();
+  let mut projects: BTreeMap<String, Totals> = BTreeMap::new();
+  for (user, entries) in machines.values() {
+    for e in converter.entries(entries).iter().filter(|e| filter.matches(e)) {
+      total.add(e);
+      users.entry(user.clone()).or_default().add(e);
+      models.entry(e.model.clone()).or_default().add(e);
+      projects.entry(project_name(e)).or_default().add(e);
+    }
+  }
+
+  Ok(TeamReport {
+    since,
+    until,
+    currency: converter.currency().to_string(),
+    bundles,
+    machines: machines.len(),
+    skipped,
+    total,
+    users: rows(users),
+    models: rows(models),
+    projects: rows(projects),
+  })
+}
+
+const COLUMNS: [&str; 7] = [
+  "requests",
+  "input_tokens",
+  "output_tokens",
+  "cache_read_tokens",
+  "cache_write_tokens",
+  "total_tokens",
+  "cost",
+];
+
+fn values(t: &Totals) -> [String; 7] {
+  [
+    t.requests.to_string(),
+    t.input_tokens.to_string(),
+    t.output_tokens.to_string(),
+    t.cache_read_tokens.to_string(),
+    t.cache_write_tokens.to_string(),
+    t.total_tokens.to_string(),
+    format!("{:.2}", t.cost),
+  ]
+}
+
+fn markdown_table(out: &mut String, title: &str, key: &str, currency: &str, rows: &[AggregateRow]) {
+  let _ = writeln!(out, "\n## {title}\n");
+  let _ = writeln!(
+    out,
+    "| {key} | Requests | Input | Output | Cache read | Cache write | Total tokens | Cost ({currency}) |"
+  );
+  let _ = writeln!(out, "| --- | ---: | ---: | ---: | ---: | ---: | ---: | ---: |");
+  for row in rows {
+    let name = row.key.replace('|', "\\|");
+    let _ = writeln!(out, "| {name} | {} |", values(&row.totals).join(" | "));
+  }
+}
+
+fn csv_field(s: &str) -> String {
+  if s.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", s.replace('"', "\"\""))
+  } else {
+    s.to_string()
+  }
+}
+
+impl TeamReport {
+  fn period(&self) -> String {
+    let date = |d: Option<NaiveDate>| d.map(|d| d.to_string());
+    match (date(self.since), date(self.until)) {
+      (Some(s), Some(u)) => format!("{s} to {u}"),
+      (Some(s), None) => format!("since {s}"),
+      (None, Some(u)) => format!("until {u}"),
+      (None, None) => "all time".to_string(),
+    }
+  }
+
+  pub fn render(&self, format: ReportFormat) -> String {
+    match format {
+      ReportFormat::Json => serde_json::to_string_pretty(self).unwrap_or_default(),
+      ReportFormat::Csv => {
+        let mut out = format!("group,key,{},currency\n", COLUMNS.join(","));
+        let _ = writeln!(out, "total,all,{},{}", values(&self.total).join(","), self.currency);
+        for (group, rows) in [("user", &self.users), ("model", &self.models), ("project", &self.projects)] {
+          for row in rows {
+            let _ = writeln!(
+              out,
+              "{group},{},{},{}",
+              csv_field(&row.key),
+              values(&row.totals).join(","),
+              self.currency
+            );
+          }
+        }
+        out
+      }
+      ReportFormat::Markdown => {
+        let mut out = format!("# Team usage report ({})\n\n", self.period());
+        let _ = writeln!(
+          out,
+          "{} bundles from {} machines: **{:.2} {}** over {} requests and {} tokens.",
+          self.bundles, self.machines, self.total.cost, self.currency, self.total.requests, self.total.total_tokens
+        );
+        markdown_table(&mut out, "By user", "User", &self.currency, &self.users);
+        markdown_table(&mut out, "By model", "Model", &self.currency, &self.models);
+        markdown_table(&mut out, "By project", "Project", &self.currency, &self.projects);
+        out
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bundle::{write_bundle, Bundle, BundleHeader, BUNDLE_FORMAT, BUNDLE_VERSION};
+
+  fn entry(timestamp: &str, model: &str, project: &str, cost: f64) -> UsageEntry {
+    UsageEntry {
+      timestamp: timestamp.to_string(),
+      tool: "Claude".to_string(),
+      model: model.to_string(),
+      input_tokens: 100,
+      output_tokens: 10,
+      total_tokens: 110,
+      cost,
+      project: Some(project.to_string()),
+      ..Default::default()
+    }
+  }
+
+  fn write(dir: &Path, file: &str, machine: &str, user: &str, entries: Vec<UsageEntry>) {
+    let header = BundleHeader {
+      format: BUNDLE_FORMAT.to_string(),
+      version: BUNDLE_VERSION,
+      machine_id: machine.to_string(),
+      host_name: String::new(),
+      user_name: user.to_string(),
+      user_id: None,
+      exported_at: String::new(),
+      sources: Vec::new(),
+    };
+    write_bundle(&dir.join(file), &Bundle { header, entries }).unwrap();
+  }
+
+  #[test]
+  fn counts_overlapping_bundles_from_one_machine_once() {
+    let dir = std::env::temp_dir().join(format!("token-viewer-{}-team-report", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let january = entry("2025-01-20T12:00:00Z", "claude-sonnet-4", "/home/ann/src/app/", 1.0);
+    let february = entry("2025-02-03T12:00:00Z", "claude-opus-4", "/home/ann/src/app", 4.0);
+    write(&dir, "ann-jan.json", "m-ann", "ann", vec![january.clone()]);
+    // The February export repeats January's entry.
+    write(&dir, "ann-feb.jsonl", "m-ann", "ann", vec![january, february]);
+    let bob = entry("2025-02-10T12:00:00Z", "claude-sonnet-4", "C:\\bob\\app", 2.0);
+    write(&dir, "bob.json", "m-bob", "bob", vec![bob]);
+    fs::write(dir.join("notes.json"), "{}").unwrap();
+
+    let report = build(&dir, None, None, &Converter::usd()).unwrap();
+    assert_eq!((report.bundles, report.machines, report.skipped.len()), (3, 2, 1));
+    assert_eq!((report.total.requests, report.total.cost), (3, 7.0));
+    let keyed = |rows: &[AggregateRow]| -> Vec<(String, u64)> {
+      rows.iter().map(|r| (r.key.clone(), r.totals.requests)).collect()
+    };
+    assert_eq!(keyed(&report.users), vec![("ann".to_string(), 2), ("bob".to_string(), 1)]);
+    assert_eq!(keyed(&report.projects), vec![("app".to_string(), 3)]);
+    assert_eq!(report.models[0].key, "claude-opus-4");
+
+    let since = NaiveDate::from_ymd_opt(2025, 2, 1);
+    let february_only = build(&dir, since, None, &Converter::usd()).unwrap();
+    assert_eq!((february_only.total.requests, february_only.total.cost), (2, 6.0));
+    assert!(february_only.render(ReportFormat::Markdown).contains("# Team usage report (since 2025-02-01)"));
+    let _ = fs::remove_dir_all(&dir);
+  }
+}
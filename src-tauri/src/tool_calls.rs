@@ -0,0 +1,144 @@
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::aggregate::{EntryFilter, Totals};
+use crate::currency::Converter;
+use crate::{PricingTable, UsageEntry};
+
+/// Usage of the requests that called one tool.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolCallRow {
+  /// Tool name as logged, e.g. `Bash`, `apply_patch` or `mcp__github__create_issue`.
+  pub name: String,
+  /// MCP server for Claude's `mcp__<server>__<tool>` names.
+  pub mcp_server: Option<String>,
+  pub calls: u64,
+  /// Requests that called the tool at least once. A request calling several
+  /// tools counts toward each, so rows overlap.
+  #[serde(flatten)]
+  pub totals: Totals,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ToolCallBreakdown {
+  /// Sorted by cost, largest first.
+  pub tools: Vec<ToolCallRow>,
+  /// Requests that called no tool.
+  pub no_tool: Totals,
+  /// Server-side web searches, billed per search on top of tokens.
+  pub web_search_requests: u64,
+  /// What those searches cost at list prices. Entry costs already include
+  /// it, so it is part of the tool and no-tool totals rather than added to them.
+  pub web_search_cost: f64,
+}
+
+fn mcp_server(name: &str) -> Option<String> {
+  let (server, _) = name.strip_prefix("mcp__")?.split_once("__")?;
+  Some(server.to_string())
+}
+
+/// Tokens and cost per tool the model called, from the entries matching
+/// `filter`, with amounts in the converter's currency and searches priced from `pricing`.
+pub fn breakdown(
+  entries: &[UsageEntry],
+  filter: &EntryFilter,
+  converter: &Converter,
+  pricing: &PricingTable,
+) -> ToolCallBreakdown {
+  let mut out = ToolCallBreakdown::default();
+  let mut rows: HashMap<&str, (u64, Totals)> = HashMap::new();
+  let entries = converter.entries(entries);
+  for e in entries.iter().filter(|e| filter.matches(e)) {
+    out.web_search_requests = out.web_search_requests.saturating_add(e.web_search_requests);
+    if e.web_search_requests > 0 {
+      let usd = pricing.web_search_cost(&e.model, e.provider, e.web_search_requests);
+      out.web_search_cost += converter.convert(usd, e.local_time().map(|t| t.date_naive()));
+    }
+    if e.tool_calls.is_empty() {
+      out.no_tool.add(e);
+      continue;
+    }
+    for name in &e.tool_calls {
+      rows.entry(name).or_default().0 += 1;
+    }
+    let called: BTreeSet<&str> = e.tool_calls.iter().map(String::as_str).collect();
+    for name in called {
+      rows.entry(name).or_default().1.add(e);
+    }
+  }
+
+  out.tools = rows
+    .into_iter()
+    .map(|(name, (calls, totals))| ToolCallRow {
+      name: name.to_string(),
+      mcp_server: mcp_server(name),
+      calls,
+      totals,
+    })
+    .collect();
+  out
+    .tools
+    .sort_by(|a, b| b.totals.cost.total_cmp(&a.totals.cost).then_with(|| a.name.cmp(&b.name)));
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::PricingInfo;
+
+  fn entry(cost: f64, tool_calls: &[&str]) -> UsageEntry {
+    UsageEntry {
+      timestamp: "2025-03-01T10:00:00+00:00".to_string(),
+      tool: "Claude".to_string(),
+      model: "claude-sonnet-4".to_string(),
+      input_tokens: 100,
+      output_tokens: 10,
+      total_tokens: 110,
+      cost,
+      tool_calls: tool_calls.iter().map(|t| t.to_string()).collect(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn counts_each_call_but_each_request_once_per_tool() {
+    let entries = vec![
+      entry(1.0, &["Bash", "Bash", "mcp__github__create_issue"]),
+      entry(2.0, &["Edit"]),
+      entry(0.5, &[]),
+    ];
+    let pricing = PricingTable::new(HashMap::new());
+    let b = breakdown(&entries, &EntryFilter::default(), &Converter::usd(), &pricing);
+    let names: Vec<&str> = b.tools.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, ["Edit", "Bash", "mcp__github__create_issue"]);
+    let bash = &b.tools[1];
+    assert_eq!((bash.calls, bash.totals.requests, bash.totals.cost), (2, 1, 1.0));
+    assert_eq!(b.tools[2].mcp_server.as_deref(), Some("github"));
+    assert_eq!(b.no_tool.cost, 0.5);
+  }
+
+  #[test]
+  fn prices_web_searches() {
+    let searched = |model: &str| UsageEntry {
+      model: model.to_string(),
+      web_search_requests: 3,
+      ..entry(1.0, &["web_search"])
+    };
+    let pricing = PricingTable::new(HashMap::from([
+      ("claude-sonnet-4".to_string(), PricingInfo { input_cost_per_token: 3e-6, ..Default::default() }),
+      ("gpt-5".to_string(), PricingInfo { input_cost_per_token: 1e-6, web_search_cost: 0.025, ..Default::default() }),
+    ]));
+    let cost = |e: UsageEntry| {
+      let b = breakdown(&[e, entry(2.0, &[])], &EntryFilter::default(), &Converter::usd(), &pricing);
+      assert_eq!(b.web_search_requests, 3);
+      b.web_search_cost
+    };
+
+    // Priced models without a listed search price fall back to $10 per 1,000 searches.
+    assert!((cost(searched("claude-sonnet-4")) - 0.03).abs() < 1e-12);
+    assert!((cost(searched("gpt-5")) - 0.075).abs() < 1e-12);
+    // Unpriced models cost nothing, searches included.
+    assert_eq!(cost(searched("no-such-model")), 0.0);
+  }
+}
@@ -15,16 +15,11 @@ import { useMemo } from 'react'
 import type React from 'react'
 
 import { useUsageData } from '../hooks/useUsageData'
-import type { UsageEntry } from '../types'
+import { costFormatter, type UsageEntry } from '../types'
 import StatsCard from './StatsCard'
 import DateTable from './DateTable'
 
 const intFmt = new Intl.NumberFormat(undefined, { maximumFractionDigits: 0 })
-const usdFmt = new Intl.NumberFormat('en-US', {
-  style: 'currency',
-  currency: 'USD',
-  maximumFractionDigits: 6,
-})
 
 const darkTooltipStyle: React.CSSProperties = {
   backgroundColor: 'rgba(11, 14, 20, 0.95)',
@@ -47,7 +42,9 @@ type DailyPoint = {
 function DailyTooltip({
   active,
   payload,
+  formatCost,
 }: {
+  formatCost: (amount: number) => string
   active?: boolean
   payload?: Array<{
     payload: {
@@ -69,7 +66,7 @@ function DailyTooltip({
       <div>Input: {intFmt.format(d.input)}</div>
       <div>Output: {intFmt.format(d.output)}</div>
       <div>Total: {intFmt.format(d.tokens)}</div>
-      <div>Cost: {usdFmt.format(d.cost)}</div>
+      <div>Cost: {formatCost(d.cost)}</div>
     </div>
   )
 }
@@ -97,7 +94,8 @@ function sumTokens(e: UsageEntry): number {
 }
 
 export default function Dashboard() {
-  const { data, loading, error, refresh } = useUsageData()
+  const { data, currency, loading, error, refresh } = useUsageData()
+  const formatCost = useMemo(() => costFormatter(currency), [currency])
 
   const totals = useMemo(() => {
     let input = 0
@@ -194,7 +192,7 @@ export default function Dashboard() {
         <StatsCard title="Total Requests" value={intFmt.format(totals.requests)} />
         <StatsCard title="Total Input Tokens" value={intFmt.format(totals.input)} />
         <StatsCard title="Total Output Tokens" value={intFmt.format(totals.output)} />
-        <StatsCard title="Total Cost" value={usdFmt.format(totals.cost)} />
+        <StatsCard title="Total Cost" value={formatCost(totals.cost)} />
       </div>
 
       <div className="grid cols-2" style={{ marginTop: 12 }}>
@@ -255,7 +253,7 @@ export default function Dashboard() {
                   width={70}
                   tickFormatter={dailyTokensFmt}
                 />
-                <Tooltip content={<DailyTooltip />} />
+                <Tooltip content={<DailyTooltip formatCost={formatCost} />} />
                 <Legend />
                 <Area
                   type="monotone"
@@ -273,7 +271,7 @@ export default function Dashboard() {
       </div>
 
       <div style={{ marginTop: 12 }}>
-        <DateTable data={data} />
+        <DateTable data={data} currency={currency} />
       </div>
     </div>
   )
@@ -1,14 +1,9 @@
 import { Fragment, useEffect, useMemo, useState } from 'react'
 import { ChevronRight, ChevronDown } from 'lucide-react'
 
-import type { UsageEntry } from '../types'
+import { costFormatter, modelKey, type Currency, type UsageEntry } from '../types'
 
 const numFmt = new Intl.NumberFormat(undefined, { maximumFractionDigits: 0 })
-const usdFmt = new Intl.NumberFormat('en-US', {
-  style: 'currency',
-  currency: 'USD',
-  maximumFractionDigits: 6,
-})
 
 type Granularity = 'day' | 'week' | 'month' | 'year' | 'all'
 
@@ -36,6 +31,7 @@ type ModelRow = {
 
 type Props = {
   data: UsageEntry[]
+  currency: Currency
 }
 
 function safeDate(ts: string): Date | null {
@@ -79,7 +75,8 @@ function isoWeekKey(d: Date): string {
   return `${isoYear}-W${String(week).padStart(2, '0')}`
 }
 
-export default function DateTable({ data }: Props) {
+export default function DateTable({ data, currency }: Props) {
+  const formatCost = useMemo(() => costFormatter(currency), [currency])
   const [granularity, setGranularity] = useState<Granularity>('day')
   const [expandedKey, setExpandedKey] = useState<string | null>(null)
 
@@ -143,10 +140,11 @@ export default function DateTable({ data }: Props) {
 
     const map = new Map<string, ModelRow>()
     for (const e of entries) {
+      const key = modelKey(e)
       const cur =
-        map.get(e.model) ??
+        map.get(key) ??
         ({
-          model: e.model,
+          model: key,
           requests: 0,
           input_tokens: 0,
           output_tokens: 0,
@@ -163,7 +161,7 @@ export default function DateTable({ data }: Props) {
       cur.cache_write_tokens += e.cache_write_tokens
       cur.total_tokens += e.total_tokens
       cur.cost += e.cost
-      map.set(e.model, cur)
+      map.set(key, cur)
     }
 
     return [...map.values()].sort((a, b) => {
@@ -178,10 +176,11 @@ export default function DateTable({ data }: Props) {
     if (granularity !== 'all') return []
     const map = new Map<string, ModelRow>()
     for (const e of data) {
+      const key = modelKey(e)
       const cur =
-        map.get(e.model) ??
+        map.get(key) ??
         ({
-          model: e.model,
+          model: key,
           requests: 0,
           input_tokens: 0,
           output_tokens: 0,
@@ -198,7 +197,7 @@ export default function DateTable({ data }: Props) {
       cur.cache_write_tokens += e.cache_write_tokens
       cur.total_tokens += e.total_tokens
       cur.cost += e.cost
-      map.set(e.model, cur)
+      map.set(key, cur)
     }
 
     return [...map.values()].sort((a, b) => {
@@ -300,7 +299,7 @@ export default function DateTable({ data }: Props) {
                     <td style={{ padding: '10px 8px', textAlign: 'right' }}>{numFmt.format(mr.cache_read_tokens)}</td>
                     <td style={{ padding: '10px 8px', textAlign: 'right' }}>{numFmt.format(mr.cache_write_tokens)}</td>
                     <td style={{ padding: '10px 8px', textAlign: 'right' }}>{numFmt.format(mr.total_tokens)}</td>
-                    <td style={{ padding: '10px 8px', textAlign: 'right', whiteSpace: 'nowrap' }}>{usdFmt.format(mr.cost)}</td>
+                    <td style={{ padding: '10px 8px', textAlign: 'right', whiteSpace: 'nowrap' }}>{formatCost(mr.cost)}</td>
                   </tr>
                 ))
               )
@@ -354,7 +353,7 @@ export default function DateTable({ data }: Props) {
                         {numFmt.format(r.total_tokens)}
                       </td>
                       <td style={{ padding: '10px 8px', textAlign: 'right', whiteSpace: 'nowrap' }}>
-                        {usdFmt.format(r.cost)}
+                        {formatCost(r.cost)}
                       </td>
                     </tr>
 
@@ -445,7 +444,7 @@ export default function DateTable({ data }: Props) {
                                             whiteSpace: 'nowrap',
                                           }}
                                         >
-                                          {usdFmt.format(mr.cost)}
+                                          {formatCost(mr.cost)}
                                         </td>
                                       </tr>
                                     ))
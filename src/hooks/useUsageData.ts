@@ -1,10 +1,11 @@
 import { invoke } from '@tauri-apps/api/core'
 import { useCallback, useEffect, useRef, useState } from 'react'
 
-import type { UsageEntry } from '../types'
+import { USD, type Currency, type UsageData, type UsageEntry } from '../types'
 
 export function useUsageData() {
   const [data, setData] = useState<UsageEntry[]>([])
+  const [currency, setCurrency] = useState<Currency>(USD)
   const [loading, setLoading] = useState(false)
   const [error, setError] = useState<string | null>(null)
   const hasFullLoaded = useRef(false)
@@ -16,8 +17,9 @@ export function useUsageData() {
       const cmd = hasFullLoaded.current
         ? 'scan_all_usage_incremental'
         : 'scan_all_usage'
-      const next = await invoke<UsageEntry[]>(cmd)
-      setData(next)
+      const next = await invoke<UsageData>(cmd)
+      setData(next.entries)
+      setCurrency(next.currency)
       hasFullLoaded.current = true
     } catch (e) {
       setError(e instanceof Error ? e.message : String(e))
@@ -30,5 +32,5 @@ export function useUsageData() {
     void refresh()
   }, [refresh])
 
-  return { data, loading, error, refresh }
+  return { data, currency, loading, error, refresh }
 }
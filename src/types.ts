@@ -8,4 +8,46 @@ export type UsageEntry = {
   cache_write_tokens: number
   total_tokens: number
   cost: number
+  logged_cost?: number
+  provider?: 'bedrock' | 'vertex_ai' | 'azure'
+  service_tier?: 'priority' | 'flex' | 'batch'
+  session_id?: string
+  sidechain?: boolean
+  agent_id?: string
+  tool_calls?: string[]
+  web_search_requests?: number
+  project?: string
+  machine_id?: string
+}
+
+/** Model name, suffixed with the provider when served through a cloud platform. */
+export function modelKey(e: UsageEntry): string {
+  return e.provider ? `${e.model} (${e.provider})` : e.model
+}
+
+/** Display currency the backend converted costs into. */
+export type Currency = {
+  code: string
+  symbol?: string | null
+}
+
+export type UsageData = {
+  currency: Currency
+  entries: UsageEntry[]
+}
+
+export const USD: Currency = { code: 'USD', symbol: '$' }
+
+/** Formats amounts in `currency`, using the backend's symbol where it has one. */
+export function costFormatter(currency: Currency): (amount: number) => string {
+  const fmt = new Intl.NumberFormat('en-US', {
+    style: 'currency',
+    currency: currency.code,
+    maximumFractionDigits: 6,
+  })
+  return (amount) =>
+    fmt
+      .formatToParts(amount)
+      .map((p) => (p.type === 'currency' && currency.symbol ? currency.symbol : p.value))
+      .join('')
 }